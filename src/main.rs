@@ -1,49 +1,56 @@
 extern crate regex;
 mod mods;
 
-use chrono::Local;
-use std::path::Path;
+use chrono::{Datelike, Days, Duration, Local, NaiveDate};
+use std::cmp::{max, min};
+use std::process::exit;
 
 use ncurses::*;
 
+use mods::agenda::agenda;
+use mods::archive::Archive;
+use mods::calendar;
+use mods::cmdline::*;
+use mods::config::*;
+use mods::format::Format;
+use mods::keymap::*;
+use mods::query::Query;
+use mods::scan::scan_dir;
+use mods::session::{list_paths, Session, Startup};
 use mods::todo::*;
 use mods::ui::*;
 use mods::utils::*;
 
 const TIMEOUT: i32 = 1000; // 1 second
 const FPS: i32 = 30;
-const INDENT_SIZE: usize = 4;
+const MAX_COUNT: usize = 9999;
+const INDENT_SIZE: usize = 4; // Of the list files, whatever `indent_size` is
 
 const SELECTED_PAIR: i16 = 1;
 const UNSELECTED_PAIR: i16 = 2;
 const HIGHLIGHT_PAIR: i16 = 3;
 const UI_PAIR: i16 = 4;
 const HELP_PAIR: i16 = 5;
+const MARKED_PAIR: i16 = 6;
 
-const USAGE: &str = "Usage: todors [-f | --file <file>] [-h | --help]";
+const USAGE: &str =
+    "Usage: todors [scan <dir> | stats | list [--where <filter>]] [-f | --file <file>]... [--format <list|md|todotxt|org>] [-h | --help]";
 const HELP: &str = r#"ToDors - a simple todo list manager in terminal.
 Author: Iurii Kondrakov <deezzir@gmail.com>
 
+    Commands:
+        scan <dir>          Add the TODO/FIXME comments found in <dir> to the list and exit.
+        stats               Print the statistics of the done items of the lists and exit.
+        list                Print the items of the lists and exit.
+            --where <filter>    Only the items matching the filter or the saved filter of that name.
+
     Options:
-        -f, --file <file>   The file to use for the todo list.
+        -f, --file <file>   The file to use for the todo list, can be given several times.
+                            A directory opens all the '.list' files in it.
+        --format <format>   The file format: 'list', 'md', 'todotxt' or 'org' (by default guessed from the extension).
         -h, --help          Show this help message.
 
-    Controls:
-        <k/up>, <j/down>                ~ Move the cursor UP/DOWN
-        <K/shift+up>, <J/shift+down>    ~ Drag item UP/DOWN
-        <g>, <G>, <h>                   ~ Jump to the TOP/BOTTOM/HALF of the list
-        <d>                             ~ Delete 'Done' item/subtask
-        <i>                             ~ Insert a new 'Todo' item
-        <a>                             ~ Add a subtask to the current 'Todo' item
-        <u>                             ~ Undo last action
-        <r>                             ~ Edit current item
-        <t>                             ~ Hide subtasks
-        <?>                             ~ Show help
-        <space>                         ~ Mark current item as 'Done'
-        <enter>                         ~ Transfer item/Save edited item
-        <esc>                           ~ Cancel editing/inserting
-        <tab>                           ~ Switch between 'Todos'/'Dones'
-        <q>, <ctrl+c>                   ~ Quit
+    Config: $XDG_CONFIG_HOME/todors/config.toml or ~/.config/todors/config.toml
 "#;
 
 const FILE_PATH: &str = "TODO.list";
@@ -52,11 +59,16 @@ const FILE_PATH: &str = "TODO.list";
 enum Mode {
     Edit,
     Normal,
+    Command,
 }
 
 enum Display {
     App,
     Help,
+    Stats,
+    Agenda,
+    Calendar,
+    Archive,
 }
 
 #[cfg(not(unix))]
@@ -64,26 +76,66 @@ compile_error! {"Windows platform is not supported."}
 fn main() {
     sig_handler_init();
 
-    let file_path: String = get_args();
-    let file_name: String = Path::new(&file_path)
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
+    let config = Config::load().unwrap_or_else(|errors| {
+        for err in errors {
+            eprintln!("[ERROR]: {err}");
+        }
+        exit(1);
+    });
+    let args = get_args(&config);
+
+    if let Subcommand::Scan(dir) = &args.command {
+        let lists = list_paths(&args.file_paths[0]).unwrap_or_else(|err| {
+            eprintln!("[ERROR]: Can't open the list: {err}");
+            exit(1);
+        });
+        scan(dir, &lists[0], args.format, config.progress);
+        return;
+    }
 
+    let startup = match args.command {
+        Subcommand::App => Startup {
+            hide_subtasks: config.hide_subtasks,
+            start_in_dones: config.start_in_dones,
+            columns: config.columns.clone(),
+        },
+        _ => Startup::default(),
+    };
+    let mut session = Session::open(&args.file_paths, args.format, startup).unwrap_or_else(|err| {
+        eprintln!("[ERROR]: {err}");
+        exit(1);
+    });
+    if let Subcommand::Stats = &args.command {
+        for line in session.stats().report() {
+            println!("{line}");
+        }
+        return;
+    }
+    if let Subcommand::List(query) = args.command {
+        list(&mut session, query, config.indent_size);
+        return;
+    }
+    session.set_progress(config.progress);
+    if let Some(days) = config.archive_after {
+        session.archive_old(days);
+    }
+
+    let mut cmdline = CmdLine::new();
+    let mut keys: Vec<i32> = Vec::new();
+    let mut count: usize = 0;
     let mut editing_cursor: usize = 0;
     let mut term_size = Vec2::new(0, 0);
     let mut timeout = 0;
 
     let mut mode: Mode = Mode::Normal;
     let mut disp: Display = Display::App;
+    let mut agenda_cur: usize = 0;
+    let mut cal_day: NaiveDate = Local::now().date_naive();
+    let mut archive: Option<Archive> = None;
+    let mut archive_month: usize = 0;
     let mut ui = UI::new();
 
-    let mut app: TodoApp = TodoApp::new();
-    app.parse(&file_path);
-
-    ncurses_init();
+    ncurses_init(&config.colors);
 
     while !ctrlc_poll() {
         getmaxyx(stdscr(), &mut term_size.y, &mut term_size.x);
@@ -98,27 +150,90 @@ fn main() {
                 {
                     ui.begin_layout(LayoutKind::Vert);
                     {
+                        let (left, done) = session.app().effort_summary();
+                        let effort = if left.is_zero() && done.is_zero() {
+                            String::new()
+                        } else {
+                            format!(" [EFFORT]: {left} left, {done} done")
+                        };
                         ui.label_styled(
                             &format!(
-                                "[CONTENT]: ({})todos and ({})dones",
-                                app.get_todos_n(false),
-                                app.get_dones_n(false)
+                                "[CONTENT]: ({})todos and ({})dones{effort}",
+                                session.app().get_todos_n(false),
+                                session.app().get_dones_n(false)
                             ),
                             UI_PAIR,
                             Some(A_BOLD()),
                         );
                         ui.label_styled(
-                            &format!("[MESSAGE]: {}", app.get_message()),
+                            &format!("[MESSAGE]: {}", session.app().get_message()),
                             UI_PAIR,
                             Some(A_BOLD()),
                         );
+                        if count > 0 || !keys.is_empty() {
+                            let count = if count > 0 {
+                                count.to_string()
+                            } else {
+                                String::new()
+                            };
+                            ui.label_styled(
+                                &format!("[PENDING]: {count}{}", keys_name(&keys)),
+                                UI_PAIR,
+                                Some(A_BOLD()),
+                            );
+                        }
+                        if mode == Mode::Command {
+                            ui.edit_label(
+                                &cmdline.text,
+                                cmdline.cursor,
+                                "[COMMAND]: :".to_string(),
+                            );
+                        }
+                        if session.app().is_in_visual() {
+                            ui.label_styled("[VISUAL]", UI_PAIR, Some(A_BOLD()));
+                        }
+                        let query = session.app().get_filter().map(|query| query.to_string());
+                        let day = session.app().get_day_filter().map(|day| day.to_string());
+                        let filter: Vec<String> = query.into_iter().chain(day).collect();
+                        if !filter.is_empty() {
+                            let filter = format!("[FILTER]: {}", filter.join(" "));
+                            ui.label_styled(&filter, UI_PAIR, Some(A_BOLD()));
+                        }
+                        if session.app().is_unblocked_view() {
+                            ui.label_styled("[UNBLOCKED]", UI_PAIR, Some(A_BOLD()));
+                        }
                     }
                     ui.end_layout();
 
                     ui.begin_layout(LayoutKind::Vert);
                     {
-                        ui.label_styled(&format!("[DATE]: {date}"), UI_PAIR, Some(A_BOLD()));
-                        ui.label_styled(&format!("[FILE]: {file_name}"), UI_PAIR, Some(A_BOLD()));
+                        let timer = session.get_timer().map_or(String::new(), |item| {
+                            let since = item.clocked_since().unwrap();
+                            format!(" [TIMER]: {}", clock(Local::now() - since))
+                        });
+                        ui.label_styled(&format!("[DATE]: {date}{timer}"), UI_PAIR, Some(A_BOLD()));
+                        ui.label_styled(
+                            &format!("[FILE]: {}", session.doc().file_name),
+                            UI_PAIR,
+                            Some(A_BOLD()),
+                        );
+                        if session.docs_n() > 1 {
+                            let lists: Vec<String> = session
+                                .docs()
+                                .map(|(doc, is_cur)| {
+                                    if is_cur {
+                                        format!("[{}]", doc.file_name)
+                                    } else {
+                                        doc.file_name.clone()
+                                    }
+                                })
+                                .collect();
+                            ui.label_styled(
+                                &format!("[LISTS]: {}", lists.join(" ")),
+                                UI_PAIR,
+                                Some(A_BOLD()),
+                            );
+                        }
                     }
                     ui.end_layout();
                 }
@@ -128,8 +243,20 @@ fn main() {
                 ui.br();
 
                 match disp {
-                    Display::App => display_app(&mut ui, &mut app, mode, editing_cursor),
-                    Display::Help => display_help(&mut ui),
+                    Display::App => {
+                        display_app(&mut ui, session.app(), &config, mode, editing_cursor)
+                    }
+                    Display::Help => display_help(&mut ui, &config.keymap),
+                    Display::Stats => display_stats(&mut ui, &session),
+                    Display::Agenda => {
+                        display_agenda(&mut ui, session.app(), &config, &mut agenda_cur)
+                    }
+                    Display::Calendar => display_calendar(&mut ui, session.app(), cal_day),
+                    Display::Archive => {
+                        if let Some(archive) = &archive {
+                            display_archive(&mut ui, archive, archive_month, &config);
+                        }
+                    }
                 }
             }
             ui.end();
@@ -145,45 +272,132 @@ fn main() {
                 Display::App => {
                     match mode {
                         Mode::Normal => {
-                            app.clear_message();
-                            match char::from_u32(key as u32).unwrap() {
-                                'k' | '\u{103}' => app.go_up(),     // 'k' or 'up'
-                                'j' | '\u{102}' => app.go_down(),   // 'j' or 'down'
-                                'K' | '\u{151}' => app.drag_up(),   // 'K' or 'shift+up'
-                                'J' | '\u{150}' => app.drag_down(), // 'J' or 'shift+down'
-                                'g' => app.go_top(),
-                                'G' => app.go_bottom(),
-                                'h' => app.go_half(),
-                                ' ' => app.mark_item(),
-                                '\n' => app.transfer_item(),
-                                'd' => app.delete_item(),
-                                'u' => app.undo(),
-                                '\t' => app.toggle_panel(),
-                                't' => app.toggle_subtasks(),
-                                '?' => disp = Display::Help,
-                                'i' => {
-                                    if let Some(cur) = app.insert_item() {
-                                        editing_cursor = cur;
-                                        mode = Mode::Edit;
-                                    }
+                            session.app().clear_message();
+                            let digit = char::from_u32(key as u32).and_then(|c| c.to_digit(10));
+                            let lookup = match digit {
+                                // A count prefix comes before any key of a sequence,
+                                // a leading '0' is left for the key bindings
+                                Some(d) if keys.is_empty() && (count > 0 || d > 0) => {
+                                    count = min(count * 10 + d as usize, MAX_COUNT);
+                                    KeyResult::Pending
                                 }
-                                'a' => {
-                                    if let Some(cur) = app.append_item() {
-                                        editing_cursor = cur;
-                                        mode = Mode::Edit;
-                                    }
+                                _ => {
+                                    keys.push(key);
+                                    config.keymap.lookup(&keys)
                                 }
-                                'r' => {
-                                    if let Some(cur) = app.edit_item() {
-                                        editing_cursor = cur;
-                                        mode = Mode::Edit;
+                            };
+                            match lookup {
+                                KeyResult::Pending => {}
+                                KeyResult::Unbound => {
+                                    keys.clear();
+                                    count = 0;
+                                }
+                                KeyResult::Command(cmd) => {
+                                    let n = max(count, 1);
+                                    keys.clear();
+                                    count = 0;
+                                    match cmd {
+                                        Command::Up => (0..n).for_each(|_| session.app().go_up()),
+                                        Command::Down => {
+                                            (0..n).for_each(|_| session.app().go_down())
+                                        }
+                                        Command::DragUp => session.app().drag_up(n),
+                                        Command::DragDown => session.app().drag_down(n),
+                                        Command::Indent => session.app().indent_item(n),
+                                        Command::Outdent => session.app().outdent_item(n),
+                                        Command::Top => session.app().go_top(),
+                                        Command::Bottom => session.app().go_bottom(),
+                                        Command::Half => session.app().go_half(),
+                                        Command::Mark => session.app().mark_item(),
+                                        Command::Transfer => session.app().transfer_item(),
+                                        Command::TransferBack => session.app().transfer_back(),
+                                        Command::Delete => session.app().delete_item(),
+                                        Command::Undo => (0..n).for_each(|_| session.undo()),
+                                        Command::TogglePanel => session.app().toggle_panel(),
+                                        Command::PrevList => session.prev_list(),
+                                        Command::NextList => session.next_list(),
+                                        Command::MoveToList => session.move_item(),
+                                        Command::Yank | Command::Cut => {
+                                            let copied = if cmd == Command::Yank {
+                                                session.yank()
+                                            } else {
+                                                session.cut()
+                                            };
+                                            if copied && config.clipboard {
+                                                copy_to_clipboard(
+                                                    session.get_register(),
+                                                    config.indent_size,
+                                                );
+                                            }
+                                        }
+                                        Command::Paste => session.paste(false),
+                                        Command::PasteChild => session.paste(true),
+                                        Command::Select => session.app().select_item(),
+                                        Command::Block => session.app().block_item(),
+                                        Command::Clock => session.toggle_timer(),
+                                        Command::Visual => session.app().toggle_visual(false),
+                                        Command::VisualSubtasks => {
+                                            session.app().toggle_visual(true)
+                                        }
+                                        Command::ClearSelection => session.app().clear_selection(),
+                                        Command::Prompt => {
+                                            cmdline.start();
+                                            mode = Mode::Command;
+                                        }
+                                        Command::ToggleSubtasks => session.app().toggle_subtasks(),
+                                        Command::ToggleFold => session.app().fold_item(None),
+                                        Command::OpenFold => session.app().fold_item(Some(false)),
+                                        Command::CloseFold => session.app().fold_item(Some(true)),
+                                        Command::OpenAllFolds => session.app().fold_all(false),
+                                        Command::CloseAllFolds => session.app().fold_all(true),
+                                        Command::Help => disp = Display::Help,
+                                        Command::Stats => disp = Display::Stats,
+                                        Command::Agenda => {
+                                            agenda_cur = 0;
+                                            disp = Display::Agenda;
+                                        }
+                                        Command::Calendar => {
+                                            cal_day = Local::now().date_naive();
+                                            disp = Display::Calendar;
+                                        }
+                                        Command::Archive => match session.open_archive() {
+                                            Ok(opened) if opened.months.is_empty() => {
+                                                session.app().set_message("Nothing archived yet.")
+                                            }
+                                            Ok(opened) => {
+                                                archive_month = opened.months.len() - 1;
+                                                archive = Some(opened);
+                                                disp = Display::Archive;
+                                            }
+                                            Err(err) => session.app().set_message(&format!(
+                                                "Can't open the archive: {err}"
+                                            )),
+                                        },
+                                        Command::Insert => {
+                                            if let Some(cur) = session.app().insert_item() {
+                                                editing_cursor = cur;
+                                                mode = Mode::Edit;
+                                            }
+                                        }
+                                        Command::Append => {
+                                            if let Some(cur) = session.app().append_item() {
+                                                editing_cursor = cur;
+                                                mode = Mode::Edit;
+                                            }
+                                        }
+                                        Command::Edit => {
+                                            if let Some(cur) = session.app().edit_item() {
+                                                editing_cursor = cur;
+                                                mode = Mode::Edit;
+                                            }
+                                        }
+                                        Command::Quit => break,
                                     }
                                 }
-                                'q' => break,
-                                _ => {}
                             }
                         }
                         Mode::Edit => {
+                            let app = session.app();
                             match key as u8 as char {
                                 '\n' | '\u{1b}' => {
                                     // Enter or Esc
@@ -197,9 +411,95 @@ fn main() {
                                 _ => app.edit_item_with(&mut editing_cursor, key),
                             }
                         }
+                        Mode::Command => match key {
+                            // Enter
+                            10 => {
+                                mode = Mode::Normal;
+                                let line = cmdline.finish();
+                                if !line.is_empty() && run_command(&line, &mut session, &config) {
+                                    break;
+                                }
+                            }
+                            // Esc
+                            27 => mode = Mode::Normal,
+                            // Tab
+                            9 => {
+                                let found = cmdline.complete();
+                                session.app().set_message(&found.join(" "));
+                            }
+                            _ => cmdline.edit(key),
+                        },
+                    }
+                    if mode == Mode::Normal {
+                        session.app().fix_cursor();
+                    }
+                }
+                Display::Agenda => {
+                    let entries: Vec<(bool, usize)> =
+                        agenda(session.app(), Local::now().date_naive())
+                            .iter()
+                            .map(|entry| (entry.in_dones, entry.index))
+                            .collect();
+                    let key = match key {
+                        KEY_UP => 'k' as i32,
+                        KEY_DOWN => 'j' as i32,
+                        key => key,
+                    };
+                    match char::from_u32(key as u32).unwrap() {
+                        'k' => agenda_cur = agenda_cur.saturating_sub(1),
+                        'j' => agenda_cur = min(agenda_cur + 1, entries.len().saturating_sub(1)),
+                        '\n' => {
+                            if let Some(&(in_dones, i)) = entries.get(agenda_cur) {
+                                session.app().reveal(in_dones, i);
+                                disp = Display::App;
+                            }
+                        }
+                        ' ' => disp = Display::App,
+                        'q' => break,
+                        _ => {}
+                    }
+                }
+                Display::Calendar => {
+                    let key = match key {
+                        KEY_LEFT => 'h' as i32,
+                        KEY_DOWN => 'j' as i32,
+                        KEY_UP => 'k' as i32,
+                        KEY_RIGHT => 'l' as i32,
+                        key => key,
+                    };
+                    match char::from_u32(key as u32).unwrap() {
+                        'h' => cal_day = cal_day - Days::new(1),
+                        'j' => cal_day = cal_day + Days::new(7),
+                        'k' => cal_day = cal_day - Days::new(7),
+                        'l' => cal_day = cal_day + Days::new(1),
+                        '\n' => {
+                            session.app().set_day_filter(cal_day);
+                            disp = Display::App;
+                        }
+                        ' ' => disp = Display::App,
+                        'q' => break,
+                        _ => {}
                     }
                 }
-                Display::Help => match char::from_u32(key as u32).unwrap() {
+                Display::Archive => {
+                    let months = archive.as_ref().map_or(0, |archive| archive.months.len());
+                    let key = match key {
+                        KEY_LEFT => 'h' as i32,
+                        KEY_RIGHT => 'l' as i32,
+                        key => key,
+                    };
+                    match char::from_u32(key as u32).unwrap() {
+                        'h' => archive_month = archive_month.saturating_sub(1),
+                        'l' => archive_month = min(archive_month + 1, months.saturating_sub(1)),
+                        ' ' => {
+                            archive = None;
+                            disp = Display::App;
+                        }
+                        'q' => break,
+                        _ => {}
+                    }
+                }
+                Display::Help | Display::Stats => match char::from_u32(key as u32).unwrap() {
                     ' ' => disp = Display::App,
                     'q' => break,
                     _ => {}
@@ -212,12 +512,121 @@ fn main() {
     }
 
     endwin();
-    app.save(&file_path).unwrap();
-
-    println!("[INFO]: Saved to '{file_path}', Bye!");
+    let mut failed = false;
+    for (file_path, result) in session.save() {
+        match result {
+            Ok(()) => println!("[INFO]: Saved to '{file_path}'."),
+            Err(err) => {
+                eprintln!("[ERROR]: Can't save '{file_path}': {err}");
+                failed = true;
+            }
+        }
+    }
+    println!("[INFO]: Bye!");
 
     if cfg!(debug_assertions) {
-        println!("{app:#?}");
+        for (doc, _) in session.docs() {
+            println!("{:#?}", doc.app);
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn scan(dir: &str, file_path: &str, format: Option<Format>, progress: Progress) {
+    let format = format.unwrap_or_else(|| Format::from_path(file_path));
+    let comments = scan_dir(dir).unwrap_or_else(|err| {
+        eprintln!("[ERROR]: Can't scan '{dir}': {err}");
+        exit(1);
+    });
+
+    let mut app = TodoApp::new();
+    app.parse(file_path, format).unwrap_or_else(|err| {
+        eprintln!("[ERROR]: {err}");
+        exit(1);
+    });
+    let stats = app.merge_scanned(dir, comments);
+    app.save(file_path, format, progress).unwrap();
+
+    println!(
+        "[INFO]: Scanned '{dir}': {} new, {} moved, {} resolved. Saved to '{file_path}'.",
+        stats.added, stats.updated, stats.resolved
+    );
+}
+
+/// Prints the items of the lists, only the ones matching `query` when given.
+fn list(session: &mut Session, query: Option<Query>, indent_size: usize) {
+    if query.is_some() {
+        for app in session.apps() {
+            app.set_filter(query.clone());
+        }
+    }
+
+    let several = session.docs_n() > 1;
+    for (doc, _) in session.docs() {
+        if several {
+            println!("{}:", doc.file_name);
+        }
+        for (item, level) in doc.app.iter_matching() {
+            let indent = " ".repeat((level + several as usize) * indent_size);
+            let mark = if item.is_active() { "[ ]" } else { "[X]" };
+            println!("{indent}{mark} {}", item.text_with_meta());
+        }
+    }
+}
+
+/// Runs a `:` command line, returns true when the app should quit.
+fn run_command(line: &str, session: &mut Session, config: &Config) -> bool {
+    let (name, arg) = line
+        .split_once(' ')
+        .map_or((line, ""), |(name, arg)| (name, arg.trim()));
+
+    match (name, arg) {
+        ("w", "") => session.save_current(),
+        ("q", "") => return true,
+        ("wq", "") => {
+            session.save_current();
+            return true;
+        }
+        ("e", file) if !file.is_empty() => session.edit(file),
+        ("sort", args) if sort_args(args).is_some() => {
+            let (key, deep) = sort_args(args).unwrap();
+            session.app().sort_items(key, deep);
+        }
+        ("filter", "") => session.app().set_filter(None),
+        ("filter", query) => match config.query(query) {
+            Ok(query) => session.app().set_filter(Some(query)),
+            Err(err) => session.app().set_message(&err),
+        },
+        ("export", file) if !file.is_empty() => session.export(file),
+        ("tag", tag) if !tag.is_empty() => session.app().tag_items(tag),
+        ("unblocked", "") => session.app().toggle_unblocked_view(),
+        ("archive", "") => session.archive(None),
+        ("archive", days) if days.parse::<u64>().is_ok() => session.archive(days.parse().ok()),
+        ("goto", n) if n.parse::<usize>().is_ok() => session.app().go_to(n.parse().unwrap()),
+        _ => {
+            let message = match COMMANDS.iter().find(|(cmd, _, _)| *cmd == name) {
+                Some((cmd, arg, _)) => format!("Usage: :{cmd} {arg}").trim_end().to_string(),
+                None => format!("Unknown command: '{name}'."),
+            };
+            session.app().set_message(&message);
+        }
+    }
+    false
+}
+
+/// The `[key] [all]` arguments of `:sort`, the text is the default key.
+fn sort_args(args: &str) -> Option<(SortKey, bool)> {
+    let mut words: Vec<&str> = args.split_whitespace().collect();
+    let deep = words.last() == Some(&"all");
+    if deep {
+        words.pop();
+    }
+    match words[..] {
+        [] => Some((SortKey::Text, deep)),
+        [key] => key.parse().ok().map(|key| (key, deep)),
+        _ => None,
     }
 }
 
@@ -230,71 +639,217 @@ fn prefix(subs_hidden: bool, has_children: bool, active: bool) -> &'static str {
     }
 }
 
-fn display_app(ui: &mut UI, app: &mut TodoApp, mode: Mode, editing_cursor: usize) {
-    ui.begin_layout(LayoutKind::Horz);
+fn priority(item: &Item) -> String {
+    item.get_priority()
+        .map_or(String::new(), |pri| format!("({pri}) "))
+}
+
+fn source(item: &Item) -> String {
+    item.get_source()
+        .map_or(String::new(), |(path, line)| format!(" <{path}:{line}>"))
+}
+
+fn schedule(item: &Item, date_format: &str) -> String {
+    let due = item
+        .get_due()
+        .map(|due| format!("due {}", due.format(date_format)));
+    let rec = item.get_recurrence().map(|rule| rule.to_string());
+    let parts: Vec<String> = due.into_iter().chain(rec).collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", parts.join(", "))
+    }
+}
+
+fn clock(time: Duration) -> String {
+    let secs = time.num_seconds();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// The time tracked on the item and its subtasks.
+fn tracked(app: &TodoApp, item: &Item) -> String {
+    let now = Local::now();
+    let total: Duration = app
+        .with_subtasks(item)
+        .iter()
+        .map(|it| it.tracked(now))
+        .sum();
+    if total.is_zero() {
+        return String::new();
+    }
+
+    let mins = total.num_minutes();
+    let running = if item.clocked_since().is_some() {
+        ", running"
+    } else {
+        ""
+    };
+    if mins < 60 {
+        format!(" [{mins}m{running}]")
+    } else {
+        format!(" [{}h {:02}m{running}]", mins / 60, mins % 60)
+    }
+}
+
+fn progress(app: &TodoApp, item: &Item, style: Progress) -> String {
+    let Some((done, total)) = app.progress(item) else {
+        return String::new();
+    };
+    if style == Progress::Bar {
+        let filled = done * 10 / total;
+        let bar = format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled));
+        format!(" [{bar}] {}%", done * 100 / total)
+    } else {
+        style
+            .cookie(done, total)
+            .map_or(String::new(), |cookie| format!(" {cookie}"))
+    }
+}
+
+/// The estimate left on the item and its subtasks, or all of it once they are done.
+fn effort(app: &TodoApp, item: &Item) -> String {
+    match app.effort(item) {
+        (left, _) if !left.is_zero() => format!(" [{left} left]"),
+        (_, total) if !total.is_zero() => format!(" [{total}]"),
+        _ => String::new(),
+    }
+}
+
+/// The id other items refer to in their `dep:` tokens and the ones still blocking the item.
+fn deps(item: &Item, blocked: bool) -> String {
+    let mut deps = item
+        .get_uid()
+        .map_or(String::new(), |uid| format!(" #{uid}"));
+    if blocked {
+        let blockers: Vec<String> = item.get_deps().iter().map(|d| format!("#{d}")).collect();
+        deps.push_str(&format!(" [locked by {}]", blockers.join(", ")));
+    }
+    deps
+}
+
+fn display_app(ui: &mut UI, app: &mut TodoApp, config: &Config, mode: Mode, editing_cursor: usize) {
+    // A board shows a column for each state of the TODO items
+    let (todo_names, done_name) = match config.columns.split_last() {
+        Some((done, todos)) => (todos.to_vec(), done.as_str()),
+        None => (vec!["TODO".to_string()], "DONE"),
+    };
+
+    if config.columns.is_empty() {
+        ui.begin_layout(LayoutKind::Horz);
+    } else {
+        ui.begin_columns(config.columns.len());
+    }
     {
-        ui.begin_layout(LayoutKind::Vert);
-        {
-            if app.is_in_todos() {
-                ui.label_styled("[TODO]", HIGHLIGHT_PAIR, None);
-            } else {
-                ui.label_styled(" TODO ", UNSELECTED_PAIR, None);
-            }
-            ui.hl();
+        for (column, name) in todo_names.iter().enumerate() {
+            let focused = match app.get_column() {
+                Some(cur) => cur == column,
+                None => app.is_in_todos(),
+            };
+            ui.begin_layout(LayoutKind::Vert);
+            {
+                if focused {
+                    ui.label_styled(&format!("[{name}]"), HIGHLIGHT_PAIR, None);
+                } else {
+                    ui.label_styled(&format!(" {name} "), UNSELECTED_PAIR, None);
+                }
+                ui.hl();
 
-            for (todo, level) in app.iter_todos() {
-                let indent = " ".repeat(level * INDENT_SIZE);
-                let prefix = prefix(app.is_subs_hidden(), todo.has_children(), todo.is_active());
-                let text = todo.get_text();
-                let todo_disp = format!("{indent}{prefix} {text}",);
+                let todos = if config.columns.is_empty() {
+                    app.iter_todos()
+                } else {
+                    app.iter_column(column)
+                };
+                for (todo, level) in todos {
+                    let indent = " ".repeat(level * config.indent_size);
+                    let prefix = prefix(
+                        app.is_subs_hidden() || todo.is_folded(),
+                        todo.has_children(),
+                        todo.is_active(),
+                    );
+                    let text = todo.get_text();
+                    let pri = priority(todo);
+                    let src = source(todo);
+                    let sched = schedule(todo, &config.date_format);
+                    let blocked = todo.is_active() && app.is_blocked(todo);
+                    let deps = deps(todo, blocked);
+                    let time = tracked(app, todo);
+                    let prog = progress(app, todo, config.progress);
+                    let est = effort(app, todo);
+                    let todo_disp =
+                        format!("{indent}{prefix} {pri}{text}{prog}{sched}{est}{time}{deps}{src}",);
 
-                if app.is_cur_todo(todo) {
-                    if app.is_in_todos() {
-                        if mode == Mode::Edit {
-                            ui.edit_label(text, editing_cursor, format!("{indent}{prefix} "));
+                    if app.is_cur_todo(todo) && (focused || app.get_column().is_none()) {
+                        if focused {
+                            if mode == Mode::Edit {
+                                ui.edit_label(
+                                    text,
+                                    editing_cursor,
+                                    format!("{indent}{prefix} {pri}"),
+                                );
+                            } else {
+                                ui.label_styled(&todo_disp, SELECTED_PAIR, None);
+                            }
                         } else {
-                            ui.label_styled(&todo_disp, SELECTED_PAIR, None);
+                            ui.label_styled(&todo_disp, UNSELECTED_PAIR, None);
                         }
+                    } else if todo.is_selected() {
+                        ui.label_styled(&todo_disp, MARKED_PAIR, None);
+                    } else if blocked {
+                        ui.label_styled(&todo_disp, 0, Some(A_DIM()));
                     } else {
-                        ui.label_styled(&todo_disp, UNSELECTED_PAIR, None);
+                        ui.label(&todo_disp);
                     }
-                } else {
-                    ui.label(&todo_disp);
                 }
             }
+            ui.end_layout();
         }
-        ui.end_layout();
 
         ui.begin_layout(LayoutKind::Vert);
         {
             if app.is_in_dones() {
-                ui.label_styled("[DONE]", HIGHLIGHT_PAIR, None);
+                ui.label_styled(&format!("[{done_name}]"), HIGHLIGHT_PAIR, None);
             } else {
-                ui.label_styled(" DONE ", UNSELECTED_PAIR, None);
+                ui.label_styled(&format!(" {done_name} "), UNSELECTED_PAIR, None);
             }
             ui.hl();
 
             for (done, level) in app.iter_dones() {
-                let indent = " ".repeat(level * INDENT_SIZE);
-                let prefix = prefix(app.is_subs_hidden(), done.has_children(), done.is_active());
+                let indent = " ".repeat(level * config.indent_size);
+                let prefix = prefix(
+                    app.is_subs_hidden() || done.is_folded(),
+                    done.has_children(),
+                    done.is_active(),
+                );
                 let text = done.get_text();
+                let pri = priority(done);
+                let src = source(done);
+                let sched = schedule(done, &config.date_format);
+                let deps = deps(done, false);
+                let time = tracked(app, done);
+                let prog = progress(app, done, config.progress);
+                let est = effort(app, done);
                 let date = if !done.is_root() {
                     String::new()
                 } else {
-                    format!("({})", done.get_date())
+                    format!("({})", done.get_date(&config.date_format))
                 };
-                let done_disp = format!("{indent}{prefix}{date} {text}",);
+                let done_disp = format!(
+                    "{indent}{prefix}{date} {pri}{text}{prog}{sched}{est}{time}{deps}{src}",
+                );
 
                 if app.is_cur_done(done) {
                     if app.is_in_dones() {
                         if mode == Mode::Edit {
-                            ui.edit_label(text, editing_cursor, format!("{indent}{prefix} "));
+                            ui.edit_label(text, editing_cursor, format!("{indent}{prefix} {pri}"));
                         } else {
                             ui.label_styled(&done_disp, SELECTED_PAIR, None);
                         }
                     } else {
                         ui.label_styled(&done_disp, UNSELECTED_PAIR, None);
                     }
+                } else if done.is_selected() {
+                    ui.label_styled(&done_disp, MARKED_PAIR, None);
                 } else {
                     ui.label(&done_disp);
                 }
@@ -305,7 +860,148 @@ fn display_app(ui: &mut UI, app: &mut TodoApp, mode: Mode, editing_cursor: usize
     ui.end_layout();
 }
 
-fn display_help(ui: &mut UI) {
+fn display_agenda(ui: &mut UI, app: &TodoApp, config: &Config, cur: &mut usize) {
+    let entries = agenda(app, Local::now().date_naive());
+    *cur = min(*cur, entries.len().saturating_sub(1));
+
+    ui.label_styled("AGENDA", UNSELECTED_PAIR, None);
+    ui.hl();
+
+    if entries.is_empty() {
+        ui.label("Nothing planned.");
+    }
+    let mut group = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if group != Some(entry.group) {
+            group = Some(entry.group);
+            ui.label_styled(entry.group.name(), UI_PAIR, Some(A_BOLD()));
+        }
+
+        let item = entry.item;
+        let check = if item.is_active() { "[ ]" } else { "[X]" };
+        let date = entry.date.map_or(String::new(), |date| {
+            format!("({}) ", date.format(&config.date_format))
+        });
+        let disp = format!("    {check} {date}{}{}", priority(item), item.get_text());
+        if i == *cur {
+            ui.label_styled(&disp, SELECTED_PAIR, None);
+        } else {
+            ui.label(&disp);
+        }
+    }
+}
+
+fn display_calendar(ui: &mut UI, app: &TodoApp, day: NaiveDate) {
+    let today = Local::now().date_naive();
+
+    ui.label_styled(
+        &format!("CALENDAR: {}", day.format("%B %Y")),
+        UNSELECTED_PAIR,
+        None,
+    );
+    ui.hl();
+
+    let names: Vec<String> = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        .iter()
+        .map(|name| format!(" {name:<10}"))
+        .collect();
+    ui.label_styled(&names.concat(), UI_PAIR, Some(A_BOLD()));
+
+    for week in calendar::month(app, day) {
+        let cells: Vec<String> = week
+            .iter()
+            .map(|cell| match cell {
+                None => " ".repeat(11),
+                Some(cell) => {
+                    let n = cell.date.day();
+                    let date = if cell.date == day {
+                        format!("[{n:>2}]")
+                    } else if cell.date == today {
+                        format!("({n:>2})")
+                    } else {
+                        format!(" {n:>2} ")
+                    };
+                    let count = |n: usize, c: char| {
+                        if n > 0 {
+                            format!("{n}{c}")
+                        } else {
+                            String::new()
+                        }
+                    };
+                    format!(
+                        "{date}{:>3}{:>3} ",
+                        count(cell.due, 'd'),
+                        count(cell.done, 'x')
+                    )
+                }
+            })
+            .collect();
+        ui.label(&cells.concat());
+    }
+    ui.br();
+
+    ui.label_styled(&day.format("%A %d %B").to_string(), UI_PAIR, Some(A_BOLD()));
+    let items = calendar::items_on(app, day);
+    if items.is_empty() {
+        ui.label("Nothing due or done.");
+    }
+    for item in items {
+        let check = if item.is_active() { "[ ]" } else { "[X]" };
+        ui.label(&format!(
+            "    {check} {}{}",
+            priority(item),
+            item.get_text()
+        ));
+    }
+}
+
+fn display_archive(ui: &mut UI, archive: &Archive, month: usize, config: &Config) {
+    ui.label_styled(&format!("ARCHIVE: {}", archive.path), UNSELECTED_PAIR, None);
+    ui.hl();
+
+    let months: Vec<String> = archive
+        .months
+        .iter()
+        .enumerate()
+        .map(|(i, (name, entries))| {
+            let n = entries.iter().filter(|(_, level)| *level == 0).count();
+            if i == month {
+                format!("[{name} ({n})]")
+            } else {
+                format!(" {name} ({n}) ")
+            }
+        })
+        .collect();
+    ui.label_styled(&months.join(" "), UI_PAIR, Some(A_BOLD()));
+    ui.br();
+
+    for (item, level) in &archive.months[month].1 {
+        let indent = " ".repeat(level * config.indent_size);
+        let date = if *level == 0 {
+            format!("({})", item.get_date(&config.date_format))
+        } else {
+            String::new()
+        };
+        ui.label(&format!(
+            "{indent}[X]{date} {}{}",
+            priority(item),
+            item.get_text()
+        ));
+    }
+}
+
+fn display_stats(ui: &mut UI, session: &Session) {
+    ui.label_styled("STATISTICS", UNSELECTED_PAIR, None);
+    ui.hl();
+
+    for line in session.stats().report() {
+        ui.label(&line);
+    }
+}
+
+fn display_help(ui: &mut UI, keymap: &Keymap) {
+    let controls = controls(keymap);
+
     ui.label_styled("CONTROLS", UNSELECTED_PAIR, None);
     ui.hl();
 
@@ -313,41 +1009,25 @@ fn display_help(ui: &mut UI) {
     {
         ui.begin_layout(LayoutKind::Vert);
         {
-            ui.label_styled("k/↑, j/↓", HELP_PAIR, None);
-            ui.label("K/SHIFT+↑, J/SHIFT+↓");
-            ui.label_styled("g, G, h", HELP_PAIR, None);
-            ui.label("d");
-            ui.label_styled("i", HELP_PAIR, None);
-            ui.label("a");
-            ui.label_styled("u", HELP_PAIR, None);
-            ui.label("r");
-            ui.label_styled("t", HELP_PAIR, None);
-            ui.label("?");
-            ui.label_styled("SPACE", HELP_PAIR, None);
-            ui.label("ENTER");
-            ui.label_styled("ESC", HELP_PAIR, None);
-            ui.label("TAB");
-            ui.label_styled("q/CTRL+c", HELP_PAIR, None);
+            for (i, (keys, _)) in controls.iter().enumerate() {
+                if i % 2 == 0 {
+                    ui.label_styled(keys, HELP_PAIR, None);
+                } else {
+                    ui.label(keys);
+                }
+            }
         }
         ui.end_layout();
 
         ui.begin_layout(LayoutKind::Vert);
         {
-            ui.label_styled("Move the cursor UP/DOWN", HELP_PAIR, None);
-            ui.label("Drag item UP/DOWN");
-            ui.label_styled("Jump to the TOP/BOTTOM/HALF of the list", HELP_PAIR, None);
-            ui.label("Delete 'Done' item/subtask");
-            ui.label_styled("Insert a new 'Todo' item", HELP_PAIR, None);
-            ui.label("Add a subtask to the current 'Todo' item");
-            ui.label_styled("Undo last action", HELP_PAIR, None);
-            ui.label("Edit current item");
-            ui.label_styled("Hide subtasks", HELP_PAIR, None);
-            ui.label("Show this help");
-            ui.label_styled("Mark current item as 'Done'", HELP_PAIR, None);
-            ui.label("Transfer item/Save edited item");
-            ui.label_styled("Cancel editing/inserting", HELP_PAIR, None);
-            ui.label("Switch between 'Todos'/'Dones'");
-            ui.label_styled("Quit", HELP_PAIR, None);
+            for (i, (_, description)) in controls.iter().enumerate() {
+                if i % 2 == 0 {
+                    ui.label_styled(description, HELP_PAIR, None);
+                } else {
+                    ui.label(description);
+                }
+            }
         }
         ui.end_layout();
     }
@@ -1,3 +1,16 @@
+pub mod agenda;
+pub mod archive;
+pub mod calendar;
+pub mod cmdline;
+pub mod config;
+pub mod effort;
+pub mod format;
+pub mod keymap;
+pub mod query;
+pub mod recur;
+pub mod scan;
+pub mod session;
+pub mod stats;
 pub mod todo;
 pub mod ui;
 pub mod utils;
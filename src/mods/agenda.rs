@@ -0,0 +1,82 @@
+use chrono::{Datelike, Days, NaiveDate};
+
+use super::todo::{Item, TodoApp};
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Group {
+    Overdue,
+    Today,
+    ThisWeek,
+    Later,
+    NoDate,
+}
+
+impl Group {
+    pub fn name(self) -> &'static str {
+        match self {
+            Group::Overdue => "Overdue",
+            Group::Today => "Today",
+            Group::ThisWeek => "This week",
+            Group::Later => "Later",
+            Group::NoDate => "No date",
+        }
+    }
+}
+
+/// An item of the agenda along with its place in the panels.
+pub struct Entry<'a> {
+    pub group: Group,
+    pub date: Option<NaiveDate>,
+    pub item: &'a Item,
+    pub in_dones: bool,
+    pub index: usize,
+}
+
+/// The active items grouped by their due dates, and the items done this week
+/// by their completion dates. The entries are sorted by group and date.
+pub fn agenda(app: &TodoApp, today: NaiveDate) -> Vec<Entry<'_>> {
+    let week_end = today + Days::new(6 - today.weekday().num_days_from_monday() as u64);
+    let week_start = week_end - Days::new(6);
+
+    let todos = app
+        .all_todos()
+        .enumerate()
+        .filter(|(_, item)| item.is_active())
+        .map(|(index, item)| {
+            let group = match item.get_due() {
+                None => Group::NoDate,
+                Some(due) if due < today => Group::Overdue,
+                Some(due) if due == today => Group::Today,
+                Some(due) if due <= week_end => Group::ThisWeek,
+                Some(_) => Group::Later,
+            };
+            Entry {
+                group,
+                date: item.get_due(),
+                item,
+                in_dones: false,
+                index,
+            }
+        });
+
+    let dones = app.all_dones().enumerate().filter_map(|(index, item)| {
+        let done = item.get_done_date();
+        let group = match done {
+            _ if done == today => Group::Today,
+            _ if week_start <= done && done < today => Group::ThisWeek,
+            _ => return None,
+        };
+        Some(Entry {
+            group,
+            date: Some(done),
+            item,
+            in_dones: true,
+            index,
+        })
+    });
+
+    let mut entries: Vec<Entry> = todos.chain(dones).collect();
+    // The sort is stable, so the items keep the order of the panels otherwise
+    entries.sort_by_key(|entry| (entry.group, entry.date));
+    entries
+}
@@ -0,0 +1,142 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+
+use super::format::Entries;
+use super::todo::Item;
+use crate::INDENT_SIZE;
+
+const MONTH_FMT: &str = "%Y-%m";
+
+/// The DONE items moved out of a list, kept in `<file>.archive` in the DONE
+/// format of the list files under a `[2023-02]` line for every month.
+pub struct Archive {
+    pub path: String,
+    pub months: Vec<(String, Entries)>,
+}
+
+impl Archive {
+    /// Reads the archive of the list at `file_path`, which is empty until something is archived.
+    pub fn open(file_path: &str) -> io::Result<Self> {
+        let path = format!("{file_path}.archive");
+        let mut archive = Self {
+            path,
+            months: Vec::new(),
+        };
+
+        let file = match File::open(&archive.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(archive),
+            Err(err) => return Err(err),
+        };
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |err: &str| {
+                let message = format!("{}:{}: {err}", archive.path, i + 1);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            };
+
+            if let Some(month) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                archive.months.push((month.to_string(), Vec::new()));
+                continue;
+            }
+            let item = Item::from_done_line(&line).map_err(invalid)?;
+            let level = (line.len() - line.trim_start().len()) / INDENT_SIZE;
+            match archive.months.last_mut() {
+                Some((_, entries)) => entries.push((item, level)),
+                None => return Err(invalid("a DONE item out of any month")),
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Files the items under the months they were done in, by their root items.
+    pub fn add(&mut self, entries: Entries) {
+        let mut month = String::new();
+        for (item, level) in entries {
+            if level == 0 {
+                month = item.get_datetime().format(MONTH_FMT).to_string();
+            }
+            let i = match self.months.iter().position(|(m, _)| *m >= month) {
+                Some(i) if self.months[i].0 == month => i,
+                Some(i) => {
+                    self.months.insert(i, (month.clone(), Vec::new()));
+                    i
+                }
+                None => {
+                    self.months.push((month.clone(), Vec::new()));
+                    self.months.len() - 1
+                }
+            };
+            self.months[i].1.push((item, level));
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut content = Vec::new();
+        for (month, entries) in &self.months {
+            writeln!(content, "[{month}]")?;
+            for (item, level) in entries {
+                writeln!(content, "{}", item.done_line(*level))?;
+            }
+        }
+        fs::write(&self.path, content)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &(Item, usize)> {
+        self.months.iter().flat_map(|(_, entries)| entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn done(line: &str) -> Item {
+        Item::from_done_line(line).unwrap()
+    }
+
+    #[test]
+    fn add_save_open() {
+        let dir = std::env::temp_dir().join(format!("todors-{}-archive", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("a.list").to_string_lossy().to_string();
+
+        let mut archive = Archive::open(&file_path).unwrap();
+        assert!(archive.months.is_empty());
+
+        // Subtasks stay with their roots, the months are kept in order
+        archive.add(vec![
+            (done("DONE(2024-03-01 10:00 +0000): b"), 0),
+            (done("DONE(2024-01-20 10:00 +0000): b1"), 1),
+        ]);
+        archive.add(vec![(done("DONE(2024-01-15 10:00 +0000): a"), 0)]);
+        archive.add(vec![(done("DONE(2024-03-05 10:00 +0000): c"), 0)]);
+        archive.save().unwrap();
+
+        let content = fs::read_to_string(&archive.path).unwrap();
+        assert_eq!(
+            content,
+            "[2024-01]\nDONE(2024-01-15 10:00 +0000): a\n\
+             [2024-03]\nDONE(2024-03-01 10:00 +0000): b\n    DONE(2024-01-20 10:00 +0000): b1\n\
+             DONE(2024-03-05 10:00 +0000): c\n"
+        );
+
+        let archive = Archive::open(&file_path).unwrap();
+        let texts: Vec<(&str, usize)> = archive
+            .items()
+            .map(|(item, level)| (item.get_text().as_str(), *level))
+            .collect();
+        assert_eq!(texts, [("a", 0), ("b", 0), ("b1", 1), ("c", 0)]);
+
+        fs::write(&archive.path, "DONE(2024-01-15 10:00 +0000): a\n").unwrap();
+        let err = Archive::open(&file_path).err().unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("a.list.archive:1: a DONE item out of any month"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::{Datelike, Days, NaiveDate};
+
+use super::todo::{Item, TodoApp};
+
+/// A day of the calendar with the number of items due and done on it.
+pub struct Day {
+    pub date: NaiveDate,
+    pub due: usize,
+    pub done: usize,
+}
+
+/// The weeks of the month of `date`, from Monday to Sunday. The days of the
+/// other months are left empty.
+pub fn month(app: &TodoApp, date: NaiveDate) -> Vec<[Option<Day>; 7]> {
+    let first = date.with_day(1).unwrap();
+    let start = first - Days::new(first.weekday().num_days_from_monday() as u64);
+
+    let mut weeks = Vec::new();
+    let mut days = start.iter_days();
+    loop {
+        let week: [Option<Day>; 7] = std::array::from_fn(|_| {
+            let date = days.next().unwrap();
+            (date.month() == first.month()).then(|| day(app, date))
+        });
+        if week.iter().all(Option::is_none) {
+            break;
+        }
+        weeks.push(week);
+    }
+    weeks
+}
+
+/// The items due or done on `date`, from both panels.
+pub fn items_on(app: &TodoApp, date: NaiveDate) -> Vec<&Item> {
+    app.all_todos()
+        .chain(app.all_dones())
+        .filter(|item| item.is_on(date))
+        .collect()
+}
+
+fn day(app: &TodoApp, date: NaiveDate) -> Day {
+    let items = items_on(app, date);
+    Day {
+        date,
+        due: items.iter().filter(|item| item.is_active()).count(),
+        done: items
+            .iter()
+            .filter(|item| !item.is_active() && item.get_done_date() == date)
+            .count(),
+    }
+}
@@ -0,0 +1,129 @@
+use ncurses::constants;
+
+use super::utils::edit_text;
+
+/// The `:` commands with their argument and description.
+pub const COMMANDS: [(&str, &str, &str); 11] = [
+    ("w", "", "Save the current list"),
+    ("q", "", "Quit, all the lists are saved on exit"),
+    ("wq", "", "Save the current list and quit"),
+    ("e", "<file>", "Open a list or switch to it"),
+    (
+        "sort",
+        "[key] [all]",
+        "Sort the panel by text, created, done, priority, due or subtasks",
+    ),
+    (
+        "filter",
+        "[filter]",
+        "Show only the items matching the filter or a saved one, none clears",
+    ),
+    (
+        "export",
+        "<file>",
+        "Write the list to a file, format by extension",
+    ),
+    ("goto", "<n>", "Jump to the n-th item of the panel"),
+    ("tag", "<tag>", "Tag the selected items or the current one"),
+    (
+        "unblocked",
+        "",
+        "Show only the items that can be done now, or all again",
+    ),
+    (
+        "archive",
+        "[days]",
+        "Archive the DONE items older than that, or all",
+    ),
+];
+
+pub struct CmdLine {
+    pub text: String,
+    pub cursor: usize,
+    history: Vec<String>,
+    hist_pos: usize,
+}
+
+impl CmdLine {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            hist_pos: 0,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.hist_pos = self.history.len();
+    }
+
+    pub fn edit(&mut self, key: i32) {
+        match key {
+            constants::KEY_UP if self.hist_pos > 0 => {
+                self.hist_pos -= 1;
+                self.text = self.history[self.hist_pos].clone();
+                self.cursor = self.text.len();
+            }
+            constants::KEY_DOWN if self.hist_pos < self.history.len() => {
+                self.hist_pos += 1;
+                self.text = self.history.get(self.hist_pos).cloned().unwrap_or_default();
+                self.cursor = self.text.len();
+            }
+            _ => edit_text(&mut self.text, &mut self.cursor, key),
+        }
+    }
+
+    /// Completes the command name as far as it is unambiguous.
+    /// Returns the matching names when there are several of them.
+    pub fn complete(&mut self) -> Vec<&'static str> {
+        if self.text.contains(' ') {
+            return Vec::new();
+        }
+
+        let found: Vec<(&str, &str)> = COMMANDS
+            .iter()
+            .filter(|(name, _, _)| name.starts_with(&self.text))
+            .map(|&(name, arg, _)| (name, arg))
+            .collect();
+
+        match found.as_slice() {
+            [] => {}
+            [(name, arg)] => {
+                self.text = name.to_string();
+                if !arg.is_empty() {
+                    self.text.push(' ');
+                }
+            }
+            [(first, _), rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |len, (name, _)| {
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                self.text = first[..common].to_string();
+            }
+        }
+        self.cursor = self.text.len();
+
+        if found.len() > 1 {
+            found.into_iter().map(|(name, _)| name).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Ends the editing, the entered line is remembered in the history.
+    pub fn finish(&mut self) -> String {
+        let line = self.text.trim().to_string();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        line
+    }
+}
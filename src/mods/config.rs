@@ -0,0 +1,338 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chrono::format::{Item, StrftimeItems};
+use ncurses::*;
+use toml::{Table, Value};
+
+use super::keymap::Keymap;
+use super::query::Query;
+use super::todo::state;
+use crate::{FILE_PATH, INDENT_SIZE};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Color {
+    Default,
+    Index(i16),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn parse(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Integer(n @ 0..=255) => Ok(Color::Index(*n as i16)),
+            Value::String(s) => match s.as_str() {
+                "default" => Ok(Color::Default),
+                "black" => Ok(Color::Index(COLOR_BLACK)),
+                "red" => Ok(Color::Index(COLOR_RED)),
+                "green" => Ok(Color::Index(COLOR_GREEN)),
+                "yellow" => Ok(Color::Index(COLOR_YELLOW)),
+                "blue" => Ok(Color::Index(COLOR_BLUE)),
+                "magenta" => Ok(Color::Index(COLOR_MAGENTA)),
+                "cyan" => Ok(Color::Index(COLOR_CYAN)),
+                "white" => Ok(Color::Index(COLOR_WHITE)),
+                hex if hex.len() == 7 && hex.starts_with('#') => {
+                    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+                    match (channel(1), channel(3), channel(5)) {
+                        (Ok(r), Ok(g), Ok(b)) => Ok(Color::Rgb(r, g, b)),
+                        _ => Err(format!("invalid color '{hex}'")),
+                    }
+                }
+                _ => Err(format!("unknown color '{s}'")),
+            },
+            _ => Err("expected a color name, a '#rrggbb' string or a number from 0 to 255".into()),
+        }
+    }
+
+    /// Turns the color into an ncurses color number. Truecolor is used when the
+    /// terminal allows redefining colors, otherwise the closest color is picked
+    /// from the 256 or the 8 color palette.
+    pub fn resolve(self, free_slot: &mut i16) -> i16 {
+        match self {
+            Color::Default => -1,
+            Color::Index(n) => n,
+            Color::Rgb(r, g, b) => {
+                if *free_slot >= 16 {
+                    let scale = |c: u8| (c as i32 * 1000 / 255) as i16;
+                    init_color(*free_slot, scale(r), scale(g), scale(b));
+                    *free_slot -= 1;
+                    *free_slot + 1
+                } else if COLORS() >= 256 {
+                    let cube = |c: u8| (c as i16 * 5 + 127) / 255;
+                    16 + 36 * cube(r) + 6 * cube(g) + cube(b)
+                } else {
+                    let bit = |c: u8, b: i16| if c > 127 { b } else { 0 };
+                    bit(r, 1) | bit(g, 2) | bit(b, 4)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ColorPair {
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl ColorPair {
+    fn new(fg: i16, bg: i16) -> Self {
+        Self {
+            fg: Color::Index(fg),
+            bg: Color::Index(bg),
+        }
+    }
+
+    fn parse(value: &Value) -> Result<Self, String> {
+        let table = value
+            .as_table()
+            .ok_or("expected a table like { fg = \"black\", bg = \"green\" }")?;
+        let mut pair = ColorPair {
+            fg: Color::Default,
+            bg: Color::Default,
+        };
+
+        for (key, value) in table {
+            match key.as_str() {
+                "fg" => pair.fg = Color::parse(value)?,
+                "bg" => pair.bg = Color::parse(value)?,
+                _ => return Err(format!("unknown key '{key}', expected 'fg' or 'bg'")),
+            }
+        }
+        Ok(pair)
+    }
+}
+
+#[derive(Debug)]
+pub struct Colors {
+    pub highlight: ColorPair,
+    pub selected: ColorPair,
+    pub unselected: ColorPair,
+    pub ui: ColorPair,
+    pub help: ColorPair,
+    pub marked: ColorPair,
+}
+
+/// How the parents show how many of their subtasks are done.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Progress {
+    Off,
+    Count,
+    Percent,
+    Bar,
+}
+
+impl Progress {
+    /// The `[3/5]` or `[60%]` cookie, also written to the exported formats.
+    pub fn cookie(self, done: usize, total: usize) -> Option<String> {
+        match self {
+            Progress::Off => None,
+            Progress::Count => Some(format!("[{done}/{total}]")),
+            Progress::Percent | Progress::Bar => Some(format!("[{}%]", done * 100 / total)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub list: String,
+    pub indent_size: usize,
+    pub date_format: String,
+    pub hide_subtasks: bool,
+    pub start_in_dones: bool,
+    pub clipboard: bool,
+    pub progress: Progress,
+    pub columns: Vec<String>,
+    pub archive_after: Option<u64>,
+    pub filters: Vec<(String, Query)>,
+    pub colors: Colors,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            list: FILE_PATH.to_string(),
+            indent_size: INDENT_SIZE,
+            date_format: "%y-%m-%d".to_string(),
+            hide_subtasks: false,
+            start_in_dones: false,
+            clipboard: false,
+            progress: Progress::Count,
+            columns: Vec::new(),
+            archive_after: None,
+            filters: Vec::new(),
+            colors: Colors {
+                highlight: ColorPair::new(COLOR_BLACK, COLOR_GREEN),
+                selected: ColorPair::new(COLOR_BLACK, COLOR_CYAN),
+                unselected: ColorPair::new(COLOR_BLACK, COLOR_WHITE),
+                ui: ColorPair::new(COLOR_WHITE, COLOR_BLACK),
+                help: ColorPair::new(COLOR_WHITE, COLOR_BLACK),
+                marked: ColorPair::new(COLOR_BLACK, COLOR_YELLOW),
+            },
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+fn as_str(value: &Value) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| "expected a string".into())
+}
+
+fn as_bool(value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| "expected true or false".into())
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{home}/{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/todors/config.toml`, falling back to `~/.config`.
+pub fn config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("todors").join("config.toml"))
+}
+
+impl Config {
+    /// Loads the config file if there is one, all the invalid options are reported at once.
+    pub fn load() -> Result<Self, Vec<String>> {
+        let mut config = Config::default();
+        let Some(path) = config_path() else {
+            return Ok(config);
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(config),
+            Err(err) => return Err(vec![format!("{}: {err}", path.display())]),
+        };
+        let table: Table = content.parse().map_err(|err: toml::de::Error| {
+            vec![format!("{}: {}", path.display(), err.message())]
+        })?;
+
+        let errors: Vec<String> = table
+            .iter()
+            .filter_map(|(key, value)| config.set(key, value).err().map(|err| (key, err)))
+            .map(|(key, err)| format!("{}: '{key}': {err}", path.display()))
+            .collect();
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The filter saved under `name`, or the query written in it.
+    pub fn query(&self, name: &str) -> Result<Query, String> {
+        match self.filters.iter().find(|(n, _)| n == name) {
+            Some((_, query)) => Ok(query.clone()),
+            None => name.parse(),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "list" => self.list = expand_home(as_str(value)?),
+            "indent_size" => match value.as_integer() {
+                Some(n @ 0..=16) => self.indent_size = n as usize,
+                _ => return Err("expected a number from 0 to 16".into()),
+            },
+            "date_format" => {
+                let fmt = as_str(value)?;
+                if StrftimeItems::new(fmt).any(|item| item == Item::Error) {
+                    return Err(format!("invalid date format '{fmt}'"));
+                }
+                self.date_format = fmt.to_string();
+            }
+            "hide_subtasks" => self.hide_subtasks = as_bool(value)?,
+            "clipboard" => self.clipboard = as_bool(value)?,
+            "progress" => {
+                self.progress = match as_str(value)? {
+                    "off" => Progress::Off,
+                    "count" => Progress::Count,
+                    "percent" => Progress::Percent,
+                    "bar" => Progress::Bar,
+                    style => {
+                        return Err(format!(
+                        "unknown progress '{style}', expected 'off', 'count', 'percent' or 'bar'"
+                    ))
+                    }
+                }
+            }
+            "columns" => {
+                let columns = value
+                    .as_array()
+                    .ok_or("expected an array of column names")?
+                    .iter()
+                    .map(as_str)
+                    .collect::<Result<Vec<&str>, String>>()?;
+                if columns.len() < 2 {
+                    return Err(
+                        "expected at least two columns, the last one is for done items".into(),
+                    );
+                }
+                for (i, name) in columns.iter().enumerate() {
+                    if name.trim().is_empty() {
+                        return Err(format!("invalid column name '{name}'"));
+                    }
+                    if columns[..i].iter().any(|other| state(other) == state(name)) {
+                        return Err(format!("duplicate column '{name}'"));
+                    }
+                }
+                self.columns = columns.into_iter().map(str::to_string).collect();
+            }
+            "archive_after" => match value.as_integer() {
+                Some(0) => self.archive_after = None,
+                Some(days @ 1..) => self.archive_after = Some(days as u64),
+                _ => return Err("expected a number of days, 0 turns it off".into()),
+            },
+            "start_panel" => match as_str(value)? {
+                "todo" => self.start_in_dones = false,
+                "done" => self.start_in_dones = true,
+                panel => {
+                    return Err(format!(
+                        "unknown panel '{panel}', expected 'todo' or 'done'"
+                    ))
+                }
+            },
+            "colors" => {
+                let table = value.as_table().ok_or("expected a table")?;
+                for (name, pair) in table {
+                    let pair = ColorPair::parse(pair).map_err(|err| format!("'{name}': {err}"))?;
+                    match name.as_str() {
+                        "highlight" => self.colors.highlight = pair,
+                        "selected" => self.colors.selected = pair,
+                        "unselected" => self.colors.unselected = pair,
+                        "ui" => self.colors.ui = pair,
+                        "help" => self.colors.help = pair,
+                        "marked" => self.colors.marked = pair,
+                        _ => return Err(format!("unknown color pair '{name}'")),
+                    }
+                }
+            }
+            "filters" => {
+                let table = value.as_table().ok_or("expected a table")?;
+                for (name, query) in table {
+                    let query = as_str(query)?
+                        .parse()
+                        .map_err(|err| format!("'{name}': {err}"))?;
+                    self.filters.push((name.clone(), query));
+                }
+            }
+            "keys" => self.keymap.configure(value)?,
+            _ => return Err("unknown option".into()),
+        }
+        Ok(())
+    }
+}
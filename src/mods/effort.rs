@@ -0,0 +1,168 @@
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+use std::str::FromStr;
+
+/// An estimate of the work left on an item, saved as a `~2h`, `~30m` or
+/// `~3pts` token. Time and points are summed up separately.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Effort {
+    minutes: u32,
+    points: f32,
+}
+
+impl Effort {
+    pub fn is_zero(&self) -> bool {
+        self.minutes == 0 && self.points == 0.0
+    }
+
+    /// Replaces the time or the points that are given in `other`.
+    pub fn update(&mut self, other: Effort) {
+        if other.minutes > 0 {
+            self.minutes = other.minutes;
+        }
+        if other.points > 0.0 {
+            self.points = other.points;
+        }
+    }
+}
+
+impl Add for Effort {
+    type Output = Effort;
+
+    fn add(self, rhs: Effort) -> Effort {
+        Effort {
+            minutes: self.minutes.saturating_add(rhs.minutes),
+            points: self.points + rhs.points,
+        }
+    }
+}
+
+impl Sum for Effort {
+    fn sum<I: Iterator<Item = Effort>>(iter: I) -> Effort {
+        iter.fold(Effort::default(), Add::add)
+    }
+}
+
+impl FromStr for Effort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Unknown estimate: '{s}'.");
+
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(err)?;
+        let (value, unit) = s.split_at(split);
+        let value: f32 = value.parse().map_err(|_| err())?;
+        if !value.is_finite() || value <= 0.0 {
+            return Err(err());
+        }
+
+        let effort = match unit {
+            "m" => Effort {
+                minutes: value.round() as u32,
+                points: 0.0,
+            },
+            "h" => Effort {
+                minutes: (value * 60.0).round() as u32,
+                points: 0.0,
+            },
+            "pt" | "pts" => Effort {
+                minutes: 0,
+                points: value,
+            },
+            // Like `1h30m`
+            _ if unit.starts_with('h') => match unit[1..].parse::<Effort>() {
+                Ok(Effort { minutes, points }) if points == 0.0 && minutes < 60 => Effort {
+                    minutes: ((value * 60.0).round() as u32).saturating_add(minutes),
+                    points: 0.0,
+                },
+                _ => return Err(err()),
+            },
+            _ => return Err(err()),
+        };
+        Ok(effort)
+    }
+}
+
+impl fmt::Display for Effort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (self.minutes / 60, self.minutes % 60) {
+            (0, 0) => {}
+            (0, mins) => parts.push(format!("~{mins}m")),
+            (hours, 0) => parts.push(format!("~{hours}h")),
+            (hours, mins) => parts.push(format!("~{hours}h{mins}m")),
+        }
+        if self.points != 0.0 {
+            parts.push(format!("~{}pts", self.points));
+        }
+        if parts.is_empty() {
+            write!(f, "~0h")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effort(s: &str) -> Effort {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(effort("30m").minutes, 30);
+        assert_eq!(effort("2h").minutes, 120);
+        assert_eq!(effort("1.5h").minutes, 90);
+        assert_eq!(effort("1h30m").minutes, 90);
+        assert_eq!(effort("3pts").points, 3.0);
+        assert_eq!(effort("1pt").points, 1.0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in ["", "h", "0h", "2", "2d", "1h60m", "1h2pts", "1h30", "2é"] {
+            assert!(s.parse::<Effort>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(effort("90m").to_string(), "~1h30m");
+        assert_eq!(effort("2h").to_string(), "~2h");
+        assert_eq!(effort("45m").to_string(), "~45m");
+        assert_eq!(effort("2.5pts").to_string(), "~2.5pts");
+        assert_eq!(Effort::default().to_string(), "~0h");
+    }
+
+    #[test]
+    fn sum() {
+        let total: Effort = ["1h30m", "45m", "3pts", "2pts"]
+            .into_iter()
+            .map(effort)
+            .sum();
+        assert_eq!(total.to_string(), "~2h15m ~5pts");
+
+        // The huge ones are clamped instead of overflowing
+        let total: Effort = ["99999999999h", "99999999999h"]
+            .into_iter()
+            .map(effort)
+            .sum();
+        assert_eq!(total.minutes, u32::MAX);
+        assert_eq!(effort("99999999999h30m").minutes, u32::MAX);
+    }
+
+    #[test]
+    fn update() {
+        let mut estimate = effort("2h");
+        estimate.update(effort("3pts"));
+        assert_eq!(estimate.to_string(), "~2h ~3pts");
+        estimate.update(effort("30m"));
+        assert_eq!(estimate.to_string(), "~30m ~3pts");
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::config::Progress;
+use super::todo::{Item, ListIter};
+
+pub mod markdown;
+pub mod org;
+pub mod todotxt;
+
+/// Items in depth-first order along with their nesting level,
+/// as produced by the format readers.
+pub type Entries = Vec<(Item, usize)>;
+
+/// A root item with its subtasks, keyed by the position of the root.
+type Subtree<'a> = (Option<usize>, Vec<(&'a Item, usize)>);
+
+/// The items of both panels in the order of the file they were read from.
+/// Each panel keeps its own order, their subtrees are interleaved by the
+/// position of the root, the new ones follow the subtree above them.
+pub fn in_file_order<'a>(todos: ListIter<'a>, dones: ListIter<'a>) -> Vec<(&'a Item, usize)> {
+    let subtrees = |iter: ListIter<'a>| {
+        let mut subtrees: Vec<Subtree> = Vec::new();
+        let mut pos = None;
+        for (item, level) in iter {
+            if level == 0 || subtrees.is_empty() {
+                pos = item.get_pos().or(pos);
+                subtrees.push((pos, Vec::new()));
+            }
+            subtrees.last_mut().unwrap().1.push((item, level));
+        }
+        subtrees
+    };
+
+    let mut todos = subtrees(todos).into_iter().peekable();
+    let mut dones = subtrees(dones).into_iter().peekable();
+    let mut items = Vec::new();
+    loop {
+        let next = match (todos.peek(), dones.peek()) {
+            (Some((todo, _)), Some((done, _))) if done < todo => dones.next(),
+            (Some(_), _) => todos.next(),
+            (None, _) => dones.next(),
+        };
+        match next {
+            Some((_, subtree)) => items.extend(subtree),
+            None => return items,
+        }
+    }
+}
+
+/// Pairs the items, in file order, with the progress cookies of the ones that
+/// have subtasks.
+pub fn with_progress<'a>(
+    todos: ListIter<'a>,
+    dones: ListIter<'a>,
+    progress: Progress,
+) -> Vec<(&'a Item, usize, Option<String>)> {
+    let items = in_file_order(todos, dones);
+    (0..items.len())
+        .map(|i| {
+            let (item, level) = items[i];
+            let subtasks: Vec<&Item> = items[i + 1..]
+                .iter()
+                .take_while(|(_, l)| *l > level)
+                .map(|(item, _)| *item)
+                .collect();
+            let done = subtasks.iter().filter(|item| !item.is_active()).count();
+            let cookie = match subtasks.len() {
+                0 => None,
+                total => progress.cookie(done, total),
+            };
+            (item, level, cookie)
+        })
+        .collect()
+}
+
+/// Tells whether the item at `i` would be read back active from a heading
+/// without a checkbox or a keyword: it is, unless all its subtasks are done.
+pub fn reads_active(items: &[(&Item, usize, Option<String>)], i: usize) -> bool {
+    let level = items[i].1;
+    let mut subtasks = items[i + 1..]
+        .iter()
+        .take_while(|(_, l, _)| *l > level)
+        .peekable();
+    subtasks.peek().is_none() || subtasks.any(|(item, _, _)| item.is_active())
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Format {
+    List,
+    Markdown,
+    TodoTxt,
+    Org,
+}
+
+impl Format {
+    /// Guesses the format by the extension. Only `todo.txt` and `done.txt` are
+    /// taken for todo.txt lists, other `.txt` files are likely plain notes.
+    pub fn from_path(file_path: &str) -> Self {
+        let path = Path::new(file_path);
+        if let Some("todo.txt" | "done.txt") = path.file_name().and_then(|name| name.to_str()) {
+            return Format::TodoTxt;
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md" | "markdown") => Format::Markdown,
+            Some("org") => Format::Org,
+            _ => Format::List,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "list" => Ok(Format::List),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "todotxt" | "todo.txt" => Ok(Format::TodoTxt),
+            "org" => Ok(Format::Org),
+            _ => Err(format!("Unknown format: '{s}'.")),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Format::List => write!(f, "list"),
+            Format::Markdown => write!(f, "md"),
+            Format::TodoTxt => write!(f, "todotxt"),
+            Format::Org => write!(f, "org"),
+        }
+    }
+}
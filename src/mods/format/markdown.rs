@@ -0,0 +1,168 @@
+use std::io::{self, BufRead, Write};
+
+use chrono::Local;
+use regex::Regex;
+
+use super::{reads_active, with_progress, Entries};
+use crate::mods::config::Progress;
+use crate::mods::todo::{Item, ListIter};
+
+const INDENT: &str = "  ";
+
+struct Heading {
+    text: String,
+    hashes: usize,
+    checked: Option<bool>,
+    notes: Vec<String>,
+}
+
+impl Heading {
+    fn into_entry(self, has_items: bool) -> (Item, usize) {
+        // Without a checkbox its state is derived from the checklist below it
+        let act_cnt = match self.checked {
+            Some(checked) => usize::from(!checked),
+            None => usize::from(!has_items),
+        };
+        let mut item = Item::new(self.text, Local::now(), None, act_cnt);
+        item.set_heading(Some(self.hashes));
+        for note in self.notes {
+            item.add_note(note);
+        }
+        (item, 0)
+    }
+}
+
+/// Reads GitHub-style checklists. Every heading becomes a root item holding
+/// the checklist below it. The other lines, code blocks included, are kept
+/// with the item above them, or before the first item, to be written back in
+/// place. The progress cookies the writer adds are dropped.
+pub fn parse(reader: impl BufRead) -> io::Result<(Vec<String>, Entries)> {
+    let re_heading =
+        Regex::new(r"^(#{1,6})\s+(?:\[( |x|X)\]\s+)?(.*?)(?:\s+\[\d+(?:/\d+|%)\])?$").unwrap();
+    let re_item =
+        Regex::new(r"^(\s*)[-*+]\s+\[( |x|X)\]\s+(.*?)(?:\s+\[\d+(?:/\d+|%)\])?$").unwrap();
+    let re_fence = Regex::new(r"^\s*(```|~~~)").unwrap();
+
+    let mut preamble = Vec::new();
+    let mut entries = Vec::new();
+    let mut heading: Option<Heading> = None;
+    let mut in_heading = false;
+    let mut indents: Vec<usize> = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        let in_code = match &fence {
+            Some(mark) => {
+                if line.trim_start().starts_with(mark.as_str()) {
+                    fence = None;
+                }
+                true
+            }
+            None => {
+                fence = re_fence.captures(&line).map(|caps| caps[1].to_string());
+                fence.is_some()
+            }
+        };
+
+        if in_code {
+            // Nothing in a code block is an item
+        } else if let Some(caps) = re_heading.captures(&line) {
+            if let Some(heading) = heading.take() {
+                entries.push(heading.into_entry(false));
+            }
+            heading = Some(Heading {
+                text: caps[3].trim().to_string(),
+                hashes: caps[1].len(),
+                checked: caps.get(2).map(|c| !c.as_str().trim().is_empty()),
+                notes: Vec::new(),
+            });
+            in_heading = true;
+            indents.clear();
+            continue;
+        } else if let Some(caps) = re_item.captures(&line) {
+            if let Some(heading) = heading.take() {
+                entries.push(heading.into_entry(true));
+            }
+
+            let indent = caps[1].replace('\t', "    ").len();
+            while indents.last().is_some_and(|&top| top >= indent) {
+                indents.pop();
+            }
+            let level = indents.len() + usize::from(in_heading);
+            indents.push(indent);
+
+            let act_cnt = if caps[2].trim().is_empty() { 1 } else { 0 };
+            entries.push((
+                Item::new(caps[3].trim().to_string(), Local::now(), None, act_cnt),
+                level,
+            ));
+            continue;
+        }
+
+        match (heading.as_mut(), entries.last_mut()) {
+            (Some(heading), _) => heading.notes.push(line),
+            (None, Some((item, _))) => item.add_note(line),
+            (None, None) => preamble.push(line),
+        }
+    }
+
+    if let Some(heading) = heading.take() {
+        entries.push(heading.into_entry(false));
+    }
+
+    Ok((preamble, entries))
+}
+
+/// Writes the items as checklists under headings, in the order they were read
+/// in. The lines kept by `parse()` are written back when `kept` holds the ones
+/// before the first item.
+pub fn write(
+    file: &mut impl Write,
+    kept: Option<&[String]>,
+    todos: ListIter,
+    dones: ListIter,
+    progress: Progress,
+) -> io::Result<()> {
+    for line in kept.unwrap_or_default() {
+        writeln!(file, "{line}")?;
+    }
+
+    let items = with_progress(todos, dones, progress);
+    let mut hashes: Option<usize> = None;
+    for (i, (item, level, cookie)) in items.iter().enumerate() {
+        let cookie = cookie
+            .as_ref()
+            .map_or(String::new(), |cookie| format!(" {cookie}"));
+
+        // Root items after a heading are headings too, or they would be read
+        // back as its subtasks
+        let heading = if *level == 0 {
+            item.get_heading().or(hashes)
+        } else {
+            None
+        };
+        if let Some(n) = heading {
+            hashes = Some(n);
+            let check = match (item.is_active(), reads_active(&items, i)) {
+                (true, false) => "[ ] ",
+                (false, true) => "[x] ",
+                _ => "",
+            };
+            writeln!(file, "{} {check}{}{cookie}", "#".repeat(n), item.get_text())?;
+        } else {
+            let indent = INDENT.repeat(level - usize::from(hashes.is_some()));
+            let check = if item.is_active() { ' ' } else { 'x' };
+            writeln!(file, "{indent}- [{check}] {}{cookie}", item.get_text())?;
+        }
+
+        if kept.is_some() {
+            for note in item.get_notes() {
+                writeln!(file, "{note}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
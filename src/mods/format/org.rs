@@ -0,0 +1,205 @@
+//! Org-mode outlines: `* TODO`/`** DONE` headlines with `CLOSED:` timestamps.
+//! A headline without a keyword is done once all of its subtasks are. The
+//! other lines, body text and drawers included, are kept with the headline
+//! above them and written back in place, the headlines in the order they
+//! were read in. Progress cookies are written before the headline text and
+//! dropped on reading.
+
+use std::io::{self, BufRead, Write};
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use regex::Regex;
+
+use super::{reads_active, with_progress, Entries};
+use crate::mods::config::Progress;
+use crate::mods::todo::{Item, ListIter};
+
+const CLOSED_FMT: &str = "%Y-%m-%d %a %H:%M";
+
+static RE_TAGS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+(:[\w@#%:]+:)$").unwrap());
+
+struct Headline {
+    text: String,
+    level: usize,
+    keyword: Option<String>,
+    closed: Option<DateTime<Local>>,
+    notes: Vec<String>,
+}
+
+impl Headline {
+    fn into_entry(self, has_children: bool) -> (Item, usize) {
+        let act_cnt = match self.keyword.as_deref() {
+            Some("DONE") => 0,
+            Some(_) => 1,
+            None => usize::from(!has_children),
+        };
+        let date = self.closed.unwrap_or_else(Local::now);
+        let mut item = Item::new(self.text, date, None, act_cnt);
+        item.parse_meta();
+        if self.keyword.is_none() {
+            item.set_heading(Some(self.level + 1));
+        }
+        for note in self.notes {
+            item.add_note(note);
+        }
+        (item, self.level)
+    }
+}
+
+/// Splits the `:tag1:tag2:` tags off the end of a headline.
+fn split_tags(text: &str) -> (&str, &str) {
+    match RE_TAGS.captures(text) {
+        Some(caps) => (
+            &text[..caps.get(0).unwrap().start()],
+            caps.get(1).unwrap().as_str(),
+        ),
+        None => (text, ""),
+    }
+}
+
+pub fn parse(reader: impl BufRead) -> io::Result<(Vec<String>, Entries)> {
+    let re_headline = Regex::new(
+        r"^(\*+)\s+(?:(TODO|DONE)(?:\s+|$))?(?:\[#([A-Z])\]\s+)?(?:\[\d+(?:/\d+|%)\]\s+)?(.*)$",
+    )
+    .unwrap();
+    let re_closed =
+        Regex::new(r"CLOSED:\s*\[(\d{4}-\d{2}-\d{2})(?:\s+\w+)?(?:\s+(\d{1,2}:\d{2}))?\]").unwrap();
+
+    let mut preamble = Vec::new();
+    let mut entries = Vec::new();
+    let mut pending: Option<Headline> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if let Some(caps) = re_headline.captures(&line) {
+            let level = caps[1].len() - 1;
+            if let Some(headline) = pending.take() {
+                let has_children = level > headline.level;
+                entries.push(headline.into_entry(has_children));
+            }
+
+            let mut text = caps[4].trim().to_string();
+            if let Some(pri) = caps.get(3) {
+                text.push_str(&format!(" pri:{}", pri.as_str()));
+            }
+            pending = Some(Headline {
+                text,
+                level,
+                keyword: caps.get(2).map(|k| k.as_str().to_string()),
+                closed: None,
+                notes: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(headline) = pending.as_mut() else {
+            preamble.push(line);
+            continue;
+        };
+        // The planning line right below the headline, the rest of it is kept.
+        // Headlines without a keyword keep theirs as it is.
+        let caps = re_closed.captures(&line).filter(|_| {
+            headline.keyword.is_some() && headline.notes.is_empty() && headline.closed.is_none()
+        });
+        match caps {
+            Some(caps) => {
+                let time = caps.get(2).map_or("00:00", |t| t.as_str());
+                headline.closed = NaiveDateTime::parse_from_str(
+                    &format!("{} {time}", &caps[1]),
+                    "%Y-%m-%d %H:%M",
+                )
+                .ok()
+                .and_then(|d| d.and_local_timezone(Local).earliest());
+
+                let rest = re_closed.replace(&line, "");
+                if !rest.trim().is_empty() {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    headline.notes.push(format!("{indent}{}", rest.trim()));
+                }
+            }
+            None => headline.notes.push(line),
+        }
+    }
+
+    if let Some(headline) = pending.take() {
+        entries.push(headline.into_entry(false));
+    }
+
+    Ok((preamble, entries))
+}
+
+/// Writes the items as headlines. The lines kept by `parse()` are written
+/// back when `kept` holds the ones before the first headline.
+pub fn write(
+    file: &mut impl Write,
+    kept: Option<&[String]>,
+    todos: ListIter,
+    dones: ListIter,
+    progress: Progress,
+) -> io::Result<()> {
+    for line in kept.unwrap_or_default() {
+        writeln!(file, "{line}")?;
+    }
+
+    let items = with_progress(todos, dones, progress);
+    for (i, (item, level, cookie)) in items.iter().enumerate() {
+        let stars = "*".repeat(level + 1);
+        // Headlines read without a keyword keep going without one while it
+        // reads back the same
+        let plain = item.get_heading().is_some() && item.is_active() == reads_active(&items, i);
+        let keyword = if plain {
+            ""
+        } else if item.is_active() {
+            "TODO "
+        } else {
+            "DONE "
+        };
+        let pri = item
+            .get_priority()
+            .map_or(String::new(), |pri| format!("[#{pri}] "));
+
+        let cookie = cookie
+            .as_ref()
+            .map_or(String::new(), |cookie| format!("{cookie} "));
+
+        // Tags go last, after the tokens of the metadata
+        let (text, tags) = split_tags(item.get_text());
+        let mut line = format!("{stars} {keyword}{pri}{cookie}{text}");
+        for token in item.meta().iter().filter(|t| !t.starts_with("pri:")) {
+            line.push(' ');
+            line.push_str(token);
+        }
+        if !tags.is_empty() {
+            line.push(' ');
+            line.push_str(tags);
+        }
+        writeln!(file, "{line}")?;
+
+        let notes = match kept {
+            Some(_) => item.get_notes(),
+            None => &[],
+        };
+        let mut notes = notes.iter().peekable();
+        if !item.is_active() && !plain {
+            let indent = " ".repeat(level + 2);
+            let closed = item.get_datetime().format(CLOSED_FMT);
+            // Org only reads the planning keywords on a single line
+            match notes.next_if(|note| is_planning(note)) {
+                Some(planning) => writeln!(file, "{indent}CLOSED: [{closed}] {}", planning.trim())?,
+                None => writeln!(file, "{indent}CLOSED: [{closed}]")?,
+            }
+        }
+        for note in notes {
+            writeln!(file, "{note}")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_planning(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("SCHEDULED:") || line.starts_with("DEADLINE:")
+}
@@ -0,0 +1,259 @@
+//! The todo.txt format (<https://github.com/todotxt/todo.txt>).
+//!
+//! The mapping is lossy where todo.txt has no equivalent:
+//! - completion dates are kept with a day precision;
+//! - the hierarchy is kept with `id:`/`parent:` keys, ids are only
+//!   given to the items with subtasks and are renumbered on every save;
+//! - `+project` and `@context` tags are kept as part of the text;
+//! - `rec:` rules are written in the `1w` form, `rec:weekly` included.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use chrono::{Local, NaiveDate};
+use regex::Regex;
+
+use super::Entries;
+use crate::mods::todo::{Item, ListIter};
+
+const DAY_FMT: &str = "%Y-%m-%d";
+
+struct Task {
+    item: Item,
+    id: Option<String>,
+    parent: Option<String>,
+}
+
+pub fn parse(reader: impl BufRead) -> io::Result<Entries> {
+    let re_done =
+        Regex::new(r"^x\s+(?:(\d{4}-\d{2}-\d{2})\s+(?:(\d{4}-\d{2}-\d{2})\s+)?)?(.*)$").unwrap();
+    let re_todo = Regex::new(r"^(?:\(([A-Z])\)\s+)?(?:(\d{4}-\d{2}-\d{2})\s+)?(.*)$").unwrap();
+    let re_link = Regex::new(r"(?:^|\s+)(id|parent):(\S+)").unwrap();
+
+    let mut tasks = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Priority and creation date go through the same `key:value`
+        // tokens as in the native format.
+        let (mut text, date, act_cnt) = if let Some(caps) = re_done.captures(&line) {
+            let date = caps
+                .get(1)
+                .and_then(|d| NaiveDate::parse_from_str(d.as_str(), DAY_FMT).ok())
+                .and_then(|d| {
+                    d.and_hms_opt(0, 0, 0)
+                        .unwrap()
+                        .and_local_timezone(Local)
+                        .earliest()
+                })
+                .unwrap_or_else(Local::now);
+            let mut text = caps[3].to_string();
+            if let Some(created) = caps.get(2) {
+                text.push_str(&format!(" created:{}", created.as_str()));
+            }
+            (text, date, 0)
+        } else {
+            let caps = re_todo.captures(&line).unwrap();
+            let mut text = caps[3].to_string();
+            if let Some(pri) = caps.get(1) {
+                text.push_str(&format!(" pri:{}", pri.as_str()));
+            }
+            if let Some(created) = caps.get(2) {
+                text.push_str(&format!(" created:{}", created.as_str()));
+            }
+            (text, Local::now(), 1)
+        };
+
+        let mut id = None;
+        let mut parent = None;
+        for caps in re_link.captures_iter(&text.clone()) {
+            match &caps[1] {
+                "id" => id = Some(caps[2].to_string()),
+                _ => parent = Some(caps[2].to_string()),
+            }
+        }
+        text = re_link.replace_all(&text, "").to_string();
+
+        let mut item = Item::new(text, date, None, act_cnt);
+        item.parse_meta();
+        tasks.push(Task { item, id, parent });
+    }
+
+    Ok(build_tree(tasks))
+}
+
+fn build_tree(tasks: Vec<Task>) -> Entries {
+    let ids: HashMap<&String, usize> = tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, task)| task.id.as_ref().map(|id| (id, i)))
+        .collect();
+
+    let mut roots = Vec::new();
+    let mut children = vec![Vec::new(); tasks.len()];
+    for (i, task) in tasks.iter().enumerate() {
+        match task.parent.as_ref().and_then(|p| ids.get(p)) {
+            Some(&p) if p != i => children[p].push(i),
+            _ => roots.push(i),
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut visited = vec![false; tasks.len()];
+    let mut stack: Vec<(usize, usize)> = roots.iter().rev().map(|&r| (r, 0)).collect();
+
+    loop {
+        while let Some((i, level)) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            order.push((i, level));
+            stack.extend(children[i].iter().rev().map(|&c| (c, level + 1)));
+        }
+
+        // Tasks caught in a `parent:` cycle end up as roots
+        match visited.iter().position(|v| !v) {
+            Some(i) => stack.push((i, 0)),
+            None => break,
+        }
+    }
+
+    let mut items: Vec<Option<Item>> = tasks.into_iter().map(|task| Some(task.item)).collect();
+    order
+        .into_iter()
+        .map(|(i, level)| (items[i].take().unwrap(), level))
+        .collect()
+}
+
+pub fn write(file: &mut impl Write, todos: ListIter, dones: ListIter) -> io::Result<()> {
+    let mut next_id = 0;
+    let mut stack: Vec<Option<usize>> = Vec::new();
+
+    for (item, level) in todos.chain(dones) {
+        let mut line = String::new();
+        let mut meta = item.meta();
+
+        if item.is_active() {
+            if let Some(pri) = item.get_priority() {
+                line.push_str(&format!("({pri}) "));
+            }
+        } else {
+            line.push_str(&format!("x {} ", item.get_done_date().format(DAY_FMT)));
+        }
+        if let Some(created) = item.get_created() {
+            line.push_str(&format!("{} ", created.format(DAY_FMT)));
+        }
+        line.push_str(item.get_text());
+
+        meta.retain(|token| {
+            !(token.starts_with("created:") || item.is_active() && token.starts_with("pri:"))
+        });
+        // Other clients only read the `1w` form of the rules
+        if let Some(rule) = item.get_recurrence() {
+            for token in meta.iter_mut().filter(|token| token.starts_with("rec:")) {
+                *token = format!("rec:{}", rule.todotxt());
+            }
+        }
+
+        stack.truncate(level);
+        if let Some(Some(parent)) = stack.last() {
+            meta.push(format!("parent:{parent}"));
+        }
+        let id = if item.has_children() {
+            next_id += 1;
+            meta.push(format!("id:{next_id}"));
+            Some(next_id)
+        } else {
+            None
+        };
+        stack.push(id);
+
+        for token in meta {
+            line.push(' ');
+            line.push_str(&token);
+        }
+        writeln!(file, "{line}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> Vec<(String, usize)> {
+        parse(text.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|(item, level)| (item.get_text().clone(), level))
+            .collect()
+    }
+
+    #[test]
+    fn priorities_and_dates() {
+        let entries =
+            parse("(A) 2024-03-01 Call mom +family\nx 2024-03-10 2024-03-02 Pay rent\n".as_bytes())
+                .unwrap();
+
+        let (todo, _) = &entries[0];
+        assert!(todo.is_active());
+        assert_eq!(todo.get_text(), "Call mom +family");
+        assert_eq!(todo.get_priority(), Some('A'));
+        assert_eq!(todo.get_created(), NaiveDate::from_ymd_opt(2024, 3, 1));
+
+        let (done, _) = &entries[1];
+        assert!(!done.is_active());
+        assert_eq!(done.get_text(), "Pay rent");
+        assert_eq!(
+            done.get_done_date(),
+            NaiveDate::from_ymd_opt(2024, 3, 10).unwrap()
+        );
+        assert_eq!(done.get_created(), NaiveDate::from_ymd_opt(2024, 3, 2));
+    }
+
+    #[test]
+    fn links() {
+        let text = "Sub b parent:1\nRoot id:1\n\nSub a id:2 parent:1\nSub sub parent:2\nOther\n";
+        assert_eq!(
+            read(text),
+            [
+                ("Root".to_string(), 0),
+                ("Sub b".to_string(), 1),
+                ("Sub a".to_string(), 1),
+                ("Sub sub".to_string(), 2),
+                ("Other".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_links() {
+        // Unknown and self parents make roots
+        assert_eq!(
+            read("A parent:9\nB id:1 parent:1\n"),
+            [("A".to_string(), 0), ("B".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let text = "A id:1 parent:3\nB id:2 parent:1\nC id:3 parent:2\nD\n";
+        let entries = read(text);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0], ("D".to_string(), 0));
+        assert_eq!(
+            entries[1..],
+            [
+                ("A".to_string(), 0),
+                ("B".to_string(), 1),
+                ("C".to_string(), 2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use ncurses::constants;
+use toml::Value;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Command {
+    Up,
+    Down,
+    DragUp,
+    DragDown,
+    Indent,
+    Outdent,
+    Top,
+    Bottom,
+    Half,
+    Delete,
+    Insert,
+    Append,
+    Undo,
+    Edit,
+    ToggleSubtasks,
+    ToggleFold,
+    OpenFold,
+    CloseFold,
+    OpenAllFolds,
+    CloseAllFolds,
+    Help,
+    Stats,
+    Agenda,
+    Calendar,
+    Archive,
+    Mark,
+    Transfer,
+    TransferBack,
+    TogglePanel,
+    PrevList,
+    NextList,
+    MoveToList,
+    Yank,
+    Cut,
+    Paste,
+    PasteChild,
+    Select,
+    Visual,
+    VisualSubtasks,
+    ClearSelection,
+    Block,
+    Clock,
+    Prompt,
+    Quit,
+}
+
+impl Command {
+    pub const ALL: [Command; 44] = [
+        Command::Up,
+        Command::Down,
+        Command::DragUp,
+        Command::DragDown,
+        Command::Indent,
+        Command::Outdent,
+        Command::Top,
+        Command::Bottom,
+        Command::Half,
+        Command::Delete,
+        Command::Insert,
+        Command::Append,
+        Command::Undo,
+        Command::Edit,
+        Command::ToggleSubtasks,
+        Command::ToggleFold,
+        Command::OpenFold,
+        Command::CloseFold,
+        Command::OpenAllFolds,
+        Command::CloseAllFolds,
+        Command::Help,
+        Command::Stats,
+        Command::Agenda,
+        Command::Calendar,
+        Command::Archive,
+        Command::Mark,
+        Command::Transfer,
+        Command::TransferBack,
+        Command::TogglePanel,
+        Command::PrevList,
+        Command::NextList,
+        Command::MoveToList,
+        Command::Yank,
+        Command::Cut,
+        Command::Paste,
+        Command::PasteChild,
+        Command::Select,
+        Command::Visual,
+        Command::VisualSubtasks,
+        Command::ClearSelection,
+        Command::Block,
+        Command::Clock,
+        Command::Prompt,
+        Command::Quit,
+    ];
+
+    /// The name used for the command in the `[keys]` section of the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Up => "up",
+            Command::Down => "down",
+            Command::DragUp => "drag_up",
+            Command::DragDown => "drag_down",
+            Command::Indent => "indent",
+            Command::Outdent => "outdent",
+            Command::Top => "top",
+            Command::Bottom => "bottom",
+            Command::Half => "half",
+            Command::Delete => "delete",
+            Command::Insert => "insert",
+            Command::Append => "append",
+            Command::Undo => "undo",
+            Command::Edit => "edit",
+            Command::ToggleSubtasks => "toggle_subtasks",
+            Command::ToggleFold => "toggle_fold",
+            Command::OpenFold => "open_fold",
+            Command::CloseFold => "close_fold",
+            Command::OpenAllFolds => "open_all_folds",
+            Command::CloseAllFolds => "close_all_folds",
+            Command::Help => "help",
+            Command::Stats => "stats",
+            Command::Agenda => "agenda",
+            Command::Calendar => "calendar",
+            Command::Archive => "archive",
+            Command::Mark => "mark",
+            Command::Transfer => "transfer",
+            Command::TransferBack => "transfer_back",
+            Command::TogglePanel => "toggle_panel",
+            Command::PrevList => "prev_list",
+            Command::NextList => "next_list",
+            Command::MoveToList => "move_to_list",
+            Command::Yank => "yank",
+            Command::Cut => "cut",
+            Command::Paste => "paste",
+            Command::PasteChild => "paste_child",
+            Command::Select => "select",
+            Command::Visual => "visual",
+            Command::VisualSubtasks => "visual_subtasks",
+            Command::ClearSelection => "clear_selection",
+            Command::Block => "block",
+            Command::Clock => "clock",
+            Command::Prompt => "command_line",
+            Command::Quit => "quit",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Command::Up => "Move the cursor UP",
+            Command::Down => "Move the cursor DOWN",
+            Command::DragUp => "Drag item UP",
+            Command::DragDown => "Drag item DOWN",
+            Command::Indent => "Make item a subtask of the one above",
+            Command::Outdent => "Move subtask out of its parent",
+            Command::Top => "Jump to the TOP of the list",
+            Command::Bottom => "Jump to the BOTTOM of the list",
+            Command::Half => "Jump to the HALF of the list",
+            Command::Delete => "Delete 'Done' item/subtask",
+            Command::Insert => "Insert a new 'Todo' item",
+            Command::Append => "Add a subtask to the current 'Todo' item",
+            Command::Undo => "Undo last action",
+            Command::Edit => "Edit current item",
+            Command::ToggleSubtasks => "Hide subtasks",
+            Command::ToggleFold => "Fold/Unfold the subtasks of current item",
+            Command::OpenFold => "Unfold the subtasks of current item",
+            Command::CloseFold => "Fold the subtasks of current item",
+            Command::OpenAllFolds => "Unfold all items",
+            Command::CloseAllFolds => "Fold all items",
+            Command::Help => "Show this help",
+            Command::Stats => "Show the statistics of the done items",
+            Command::Agenda => "Show the items grouped by date",
+            Command::Calendar => "Show the month calendar",
+            Command::Archive => "Browse the archived items",
+            Command::Mark => "Mark current item as 'Done'",
+            Command::Transfer => "Transfer item/Move it to the next column",
+            Command::TransferBack => "Move item to the previous column",
+            Command::TogglePanel => "Switch between 'Todos'/'Dones' or the columns",
+            Command::PrevList => "Switch to the PREVIOUS list",
+            Command::NextList => "Switch to the NEXT list",
+            Command::MoveToList => "Move item to the next list",
+            Command::Yank => "Copy item with its subtasks",
+            Command::Cut => "Cut item with its subtasks",
+            Command::Paste => "Paste below the current item",
+            Command::PasteChild => "Paste as subtasks of the current item",
+            Command::Select => "Select/Unselect current item",
+            Command::Visual => "Select the items the cursor moves over",
+            Command::VisualSubtasks => "Same, along with their subtasks",
+            Command::ClearSelection => "Clear the selection",
+            Command::Block => "Block current item by the selected ones",
+            Command::Clock => "Start/Stop the timer of current item",
+            Command::Prompt => "Enter a ':' command",
+            Command::Quit => "Quit",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Command> {
+        Command::ALL.into_iter().find(|cmd| cmd.name() == name)
+    }
+}
+
+const KEY_NAMES: [(&str, i32); 11] = [
+    ("<up>", constants::KEY_UP),
+    ("<down>", constants::KEY_DOWN),
+    ("<left>", constants::KEY_LEFT),
+    ("<right>", constants::KEY_RIGHT),
+    ("<S-up>", constants::KEY_SR),
+    ("<S-down>", constants::KEY_SF),
+    ("<space>", ' ' as i32),
+    ("<enter>", '\n' as i32),
+    ("<tab>", '\t' as i32),
+    ("<bs>", constants::KEY_BACKSPACE),
+    ("<esc>", 27),
+];
+
+/// Parses a key sequence like `gg`, `<S-up>` or `d<space>`.
+fn parse_keys(seq: &str) -> Result<Vec<i32>, String> {
+    let mut keys = Vec::new();
+    let mut rest = seq;
+
+    while let Some(c) = rest.chars().next() {
+        let named = KEY_NAMES
+            .iter()
+            .find(|(name, _)| rest.starts_with(name) && c == '<');
+        match named {
+            Some((name, key)) => {
+                keys.push(*key);
+                rest = &rest[name.len()..];
+            }
+            None if c.is_ascii_graphic() => {
+                keys.push(c as i32);
+                rest = &rest[1..];
+            }
+            None => return Err(format!("invalid key in '{seq}'")),
+        }
+    }
+
+    if keys.is_empty() {
+        Err("empty key sequence".into())
+    } else {
+        Ok(keys)
+    }
+}
+
+pub fn key_name(key: i32) -> String {
+    match KEY_NAMES.iter().find(|(_, k)| *k == key) {
+        Some((name, _)) => name.to_string(),
+        None => char::from_u32(key as u32).map_or(format!("<{key}>"), |c| c.to_string()),
+    }
+}
+
+pub enum KeyResult {
+    Command(Command),
+    Pending,
+    Unbound,
+}
+
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Vec<i32>, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let defaults = [
+            ("k", Command::Up),
+            ("<up>", Command::Up),
+            ("j", Command::Down),
+            ("<down>", Command::Down),
+            ("K", Command::DragUp),
+            ("<S-up>", Command::DragUp),
+            ("J", Command::DragDown),
+            ("<S-down>", Command::DragDown),
+            (">", Command::Indent),
+            ("<", Command::Outdent),
+            ("g", Command::Top),
+            ("G", Command::Bottom),
+            ("h", Command::Half),
+            ("d", Command::Delete),
+            ("i", Command::Insert),
+            ("a", Command::Append),
+            ("u", Command::Undo),
+            ("r", Command::Edit),
+            ("t", Command::ToggleSubtasks),
+            ("za", Command::ToggleFold),
+            ("zo", Command::OpenFold),
+            ("zc", Command::CloseFold),
+            ("zR", Command::OpenAllFolds),
+            ("zM", Command::CloseAllFolds),
+            ("?", Command::Help),
+            ("S", Command::Stats),
+            ("A", Command::Agenda),
+            ("C", Command::Calendar),
+            ("R", Command::Archive),
+            ("<space>", Command::Mark),
+            ("<enter>", Command::Transfer),
+            ("<bs>", Command::TransferBack),
+            ("<tab>", Command::TogglePanel),
+            ("[", Command::PrevList),
+            ("]", Command::NextList),
+            ("M", Command::MoveToList),
+            ("y", Command::Yank),
+            ("x", Command::Cut),
+            ("p", Command::Paste),
+            ("P", Command::PasteChild),
+            ("m", Command::Select),
+            ("v", Command::Visual),
+            ("V", Command::VisualSubtasks),
+            ("<esc>", Command::ClearSelection),
+            ("b", Command::Block),
+            ("c", Command::Clock),
+            (":", Command::Prompt),
+            ("q", Command::Quit),
+        ];
+
+        Self {
+            bindings: defaults
+                .into_iter()
+                .map(|(keys, cmd)| (parse_keys(keys).unwrap(), cmd))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Applies the `[keys]` section of the config, where every command given
+    /// there gets its default keys replaced with the listed sequences.
+    pub fn configure(&mut self, value: &Value) -> Result<(), String> {
+        let table = value.as_table().ok_or("expected a table")?;
+
+        for (name, seqs) in table {
+            let cmd = Command::from_name(name).ok_or(format!("unknown command '{name}'"))?;
+            let seqs = match seqs {
+                Value::String(seq) => vec![seq.as_str()],
+                Value::Array(seqs) => seqs
+                    .iter()
+                    .map(|seq| seq.as_str().ok_or(format!("'{name}': expected strings")))
+                    .collect::<Result<_, _>>()?,
+                _ => {
+                    return Err(format!(
+                        "'{name}': expected a key sequence or a list of them"
+                    ))
+                }
+            };
+
+            self.bindings.retain(|(_, c)| *c != cmd);
+            for seq in seqs {
+                let keys = parse_keys(seq).map_err(|err| format!("'{name}': {err}"))?;
+                self.bindings.retain(|(k, _)| *k != keys);
+                self.bindings.push((keys, cmd));
+            }
+        }
+
+        // A sequence can never be reached when a shorter one is its prefix
+        for (keys, cmd) in &self.bindings {
+            for (other, other_cmd) in &self.bindings {
+                if other.len() < keys.len() && keys.starts_with(other) {
+                    return Err(format!(
+                        "'{}' of '{}' is shadowed by '{}' of '{}'",
+                        keys_name(keys),
+                        cmd.name(),
+                        keys_name(other),
+                        other_cmd.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, keys: &[i32]) -> KeyResult {
+        if let Some((_, cmd)) = self.bindings.iter().find(|(k, _)| k == keys) {
+            KeyResult::Command(*cmd)
+        } else if self.bindings.iter().any(|(k, _)| k.starts_with(keys)) {
+            KeyResult::Pending
+        } else {
+            KeyResult::Unbound
+        }
+    }
+
+    /// Every command with the keys bound to it, in the order of `Command::ALL`.
+    /// The description ends with the name of the command in the config.
+    pub fn help(&self) -> Vec<(String, String)> {
+        Command::ALL
+            .into_iter()
+            .filter_map(|cmd| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, c)| *c == cmd)
+                    .map(|(keys, _)| keys_name(keys))
+                    .collect();
+                (!keys.is_empty()).then(|| {
+                    (
+                        keys.join(", "),
+                        format!("{} ({})", cmd.description(), cmd.name()),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn keys_name(keys: &[i32]) -> String {
+    keys.iter().map(|&key| key_name(key)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(config: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        keymap.configure(&config.parse::<Value>().unwrap())?;
+        Ok(keymap)
+    }
+
+    fn keys(seq: &str) -> Vec<i32> {
+        parse_keys(seq).unwrap()
+    }
+
+    #[test]
+    fn parse_sequences() {
+        assert_eq!(keys("gg"), ['g' as i32, 'g' as i32]);
+        assert_eq!(keys("<S-up>"), [constants::KEY_SR]);
+        assert_eq!(keys("d<space>"), ['d' as i32, ' ' as i32]);
+        assert_eq!(keys("<"), ['<' as i32]);
+        assert_eq!(keys("<x"), ['<' as i32, 'x' as i32]);
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("a b").is_err());
+        assert_eq!(keys_name(&keys("d<space>")), "d<space>");
+    }
+
+    #[test]
+    fn lookup_sequences() {
+        let keymap = keymap("top = \"gg\"\ndelete = [\"dd\", \"D\"]").unwrap();
+        assert!(matches!(keymap.lookup(&keys("g")), KeyResult::Pending));
+        assert!(matches!(
+            keymap.lookup(&keys("gg")),
+            KeyResult::Command(Command::Top)
+        ));
+        assert!(matches!(
+            keymap.lookup(&keys("D")),
+            KeyResult::Command(Command::Delete)
+        ));
+        // The default keys of the configured commands are gone
+        assert!(matches!(keymap.lookup(&keys("d")), KeyResult::Pending));
+        assert!(matches!(keymap.lookup(&keys("gx")), KeyResult::Unbound));
+    }
+
+    #[test]
+    fn configure_errors() {
+        assert!(keymap("jump = \"x\"")
+            .unwrap_err()
+            .contains("unknown command 'jump'"));
+        assert!(keymap("top = 1").is_err());
+        assert!(keymap("top = [\"\"]").is_err());
+        // `g` still goes to the top, `gg` could never be reached
+        let err = keymap("bottom = \"gg\"").unwrap_err();
+        assert_eq!(err, "'gg' of 'bottom' is shadowed by 'g' of 'top'");
+    }
+
+    #[test]
+    fn rebinding_takes_the_keys_over() {
+        let keymap = keymap("up = \"j\"").unwrap();
+        assert!(matches!(
+            keymap.lookup(&keys("j")),
+            KeyResult::Command(Command::Up)
+        ));
+        let help = keymap.help();
+        assert!(help.contains(&("j".to_string(), "Move the cursor UP (up)".to_string())));
+        assert!(help.contains(&(
+            "<down>".to_string(),
+            "Move the cursor DOWN (down)".to_string()
+        )));
+    }
+}
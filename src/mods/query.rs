@@ -0,0 +1,382 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Days, NaiveDate};
+
+use super::todo::Item;
+
+const DAY_FMT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            Op::Eq => ord == Ordering::Equal,
+            Op::Lt => ord == Ordering::Less,
+            Op::Le => ord != Ordering::Greater,
+            Op::Gt => ord == Ordering::Greater,
+            Op::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Field {
+    Due,
+    Created,
+    Done,
+}
+
+/// A date of a query, the relative ones are counted from the day the query is run.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum When {
+    On(NaiveDate),
+    In(i64),
+    Never,
+}
+
+impl When {
+    fn resolve(self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            When::On(date) => Some(date),
+            When::In(days) if days >= 0 => today.checked_add_days(Days::new(days as u64)),
+            When::In(days) => today.checked_sub_days(Days::new(days.unsigned_abs())),
+            When::Never => None,
+        }
+    }
+}
+
+impl FromStr for When {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Unknown date: '{s}'.");
+
+        let when = match s {
+            "none" => When::Never,
+            "today" => When::In(0),
+            "tomorrow" => When::In(1),
+            "yesterday" => When::In(-1),
+            _ if s.len() == 10 => {
+                When::On(NaiveDate::parse_from_str(s, DAY_FMT).map_err(|_| err())?)
+            }
+            _ => {
+                let (n, unit) = match s.char_indices().last() {
+                    Some((i, _)) => s.split_at(i),
+                    None => return Err(err()),
+                };
+                let n: i64 = n.parse().map_err(|_| err())?;
+                match unit {
+                    "d" => When::In(n),
+                    "w" => When::In(n.checked_mul(7).ok_or_else(err)?),
+                    _ => return Err(err()),
+                }
+            }
+        };
+        Ok(when)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Tag(String),
+    Text(String),
+    Priority(Op, char),
+    Date(Field, Op, When),
+    Done,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, item: &Item, today: NaiveDate) -> bool {
+        match self {
+            Expr::Tag(tag) => item.has_tag(tag),
+            Expr::Text(text) => item.get_text().to_lowercase().contains(text),
+            Expr::Priority(op, pri) => item.get_priority().is_some_and(|p| op.holds(p.cmp(pri))),
+            Expr::Date(field, op, when) => {
+                let date = match field {
+                    Field::Due => item.get_due(),
+                    Field::Created => item.get_created(),
+                    Field::Done => (!item.is_active()).then(|| item.get_done_date()),
+                };
+                if *when == When::Never {
+                    return date.is_none() && *op == Op::Eq;
+                }
+                match (date, when.resolve(today)) {
+                    (Some(date), Some(when)) => op.holds(date.cmp(&when)),
+                    _ => false,
+                }
+            }
+            Expr::Done => !item.is_active(),
+            Expr::Not(expr) => !expr.matches(item, today),
+            Expr::And(a, b) => a.matches(item, today) && b.matches(item, today),
+            Expr::Or(a, b) => a.matches(item, today) || b.matches(item, today),
+        }
+    }
+}
+
+/// A filter like `tag:work and due<7d and not done`, see the README for the terms.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Query {
+    source: String,
+    expr: Expr,
+}
+
+impl Query {
+    pub fn matches(&self, item: &Item, today: NaiveDate) -> bool {
+        self.expr.matches(item, today)
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spaced = s.replace('(', " ( ").replace(')', " ) ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+        if tokens.is_empty() {
+            return Err("Empty filter.".into());
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Query {
+                source: tokens_join(&parser.tokens),
+                expr,
+            }),
+            Some(token) => Err(format!("Unexpected '{token}' in the filter.")),
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn tokens_join(tokens: &[&str]) -> String {
+    tokens.join(" ").replace("( ", "(").replace(" )", ")")
+}
+
+/// Reads the words of a query, `not` binds tighter than `and`, which binds
+/// tighter than `or`. Terms next to each other are joined by `and`.
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some("or") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some("and") => {
+                    self.next();
+                }
+                None | Some("or" | ")") => return Ok(expr),
+                Some(_) => {}
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some("not") => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some("(") => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err("Missing ')' in the filter.".into()),
+                }
+            }
+            Some(token @ ("and" | "or" | ")")) => {
+                Err(format!("Unexpected '{token}' in the filter."))
+            }
+            Some(token) => term(token),
+            None => Err("Unexpected end of the filter.".into()),
+        }
+    }
+}
+
+fn term(token: &str) -> Result<Expr, String> {
+    if token == "done" {
+        return Ok(Expr::Done);
+    }
+    let Some(split) = token.find([':', '<', '>', '=']) else {
+        // A bare word is a tag, like `:filter work` always was
+        return Ok(Expr::Tag(
+            token.trim_start_matches(['+', '@', '#']).to_string(),
+        ));
+    };
+
+    let (key, rest) = token.split_at(split);
+    // `due:<7d` reads the same as `due<7d`
+    let rest = match rest.strip_prefix(':') {
+        Some(op) if op.starts_with(['<', '>', '=']) => op,
+        _ => rest,
+    };
+    let (op, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Op::Le, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Op::Ge, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Op::Lt, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Op::Gt, value)
+    } else {
+        (Op::Eq, &rest[1..])
+    };
+    if value.is_empty() {
+        return Err(format!("No value for '{key}' in the filter."));
+    }
+
+    let expr = match (key, op) {
+        ("tag", Op::Eq) => Expr::Tag(value.trim_start_matches(['+', '@', '#']).to_string()),
+        ("text", Op::Eq) => Expr::Text(value.to_lowercase()),
+        ("pri", _) => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(pri @ 'A'..='Z'), None) => Expr::Priority(op, pri),
+                _ => return Err(format!("Unknown priority: '{value}'.")),
+            }
+        }
+        ("due", _) => Expr::Date(Field::Due, op, value.parse()?),
+        ("created", _) => Expr::Date(Field::Created, op, value.parse()?),
+        ("done", _) => Expr::Date(Field::Done, op, value.parse()?),
+        ("tag" | "text", _) => return Err(format!("Only '{key}:' goes with '{key}'.")),
+        _ => return Err(format!("Unknown filter key: '{key}'.")),
+    };
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    fn item(text: &str) -> Item {
+        let mut item = Item::new(text.to_string(), Local::now(), None, 1);
+        item.parse_meta();
+        item
+    }
+
+    fn matches(query: &str, text: &str) -> bool {
+        query
+            .parse::<Query>()
+            .unwrap()
+            .matches(&item(text), today())
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!("today".parse(), Ok(When::In(0)));
+        assert_eq!("7d".parse(), Ok(When::In(7)));
+        assert_eq!("-2w".parse(), Ok(When::In(-14)));
+        assert_eq!(
+            When::In(-14).resolve(today()),
+            NaiveDate::from_ymd_opt(2024, 3, 1)
+        );
+
+        assert!(matches("due<7d", "a due:2024-03-20"));
+        assert!(!matches("due<7d", "a due:2024-03-22"));
+        assert!(matches("due:<=today", "a due:2024-03-15"));
+        assert!(matches("due>-2w", "a due:2024-03-02"));
+        assert!(matches("due:none", "a"));
+        assert!(!matches("due:none", "a due:2024-03-20"));
+    }
+
+    #[test]
+    fn priorities() {
+        assert!(matches("pri<C", "a pri:A"));
+        assert!(matches("pri<C", "a pri:B"));
+        assert!(!matches("pri<C", "a pri:C"));
+        assert!(!matches("pri<C", "a"));
+        assert!(matches("pri:C", "a pri:C"));
+        assert!("pri<c".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        let not_and = "not tag:a and tag:b".parse::<Query>().unwrap();
+        assert_eq!(
+            not_and.expr,
+            Expr::And(
+                Box::new(Expr::Not(Box::new(Expr::Tag("a".into())))),
+                Box::new(Expr::Tag("b".into())),
+            )
+        );
+
+        assert!(matches("tag:a or tag:b and tag:c", "x +a"));
+        assert!(!matches("(tag:a or tag:b) and tag:c", "x +a"));
+        assert!(matches("(tag:a or tag:b) and tag:c", "x +b +c"));
+    }
+
+    #[test]
+    fn implicit_and() {
+        let implicit = "work due<7d".parse::<Query>().unwrap();
+        let explicit = "work and due<7d".parse::<Query>().unwrap();
+        assert_eq!(implicit.expr, explicit.expr);
+
+        assert!(matches("work due<7d", "a +work due:2024-03-16"));
+        assert!(!matches("work due<7d", "a +home due:2024-03-16"));
+    }
+
+    #[test]
+    fn errors() {
+        for query in [
+            "", "and", "tag:a or", "(tag:a", "tag:a)", "due:", "due<soon", "foo:bar",
+        ] {
+            assert!(query.parse::<Query>().is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn multibyte_values() {
+        assert!("due<5é".parse::<Query>().is_err());
+        assert!("due<é".parse::<Query>().is_err());
+        assert!(matches("text:café", "Café with Bob"));
+    }
+
+    #[test]
+    fn display() {
+        let query: Query = "  work  and (not   done)".parse().unwrap();
+        assert_eq!(query.to_string(), "work and (not done)");
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("mon", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("sun", Weekday::Sun),
+];
+
+/// How often a task comes back, saved as a `rec:` token: `daily`, `weekly`,
+/// `monthly`, `yearly`, every N days/weeks/months (`3d`, `2w`, `6m`) or on
+/// some days of the week (`mon,thu` or `weekdays`).
+#[derive(Clone, PartialEq, Debug)]
+pub enum Every {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Weekdays(Vec<Weekday>),
+}
+
+/// A rule along with the `+` todo.txt clients write for the strict
+/// recurrence, which is kept to be written back.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Recurrence {
+    every: Every,
+    strict: bool,
+}
+
+impl Recurrence {
+    /// The first date of the occurrence after `date`, `None` past the end of the calendar.
+    pub fn next(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match &self.every {
+            Every::Days(n) => date.checked_add_days(Days::new(*n as u64)),
+            Every::Weeks(n) => date.checked_add_days(Days::new(7 * *n as u64)),
+            Every::Months(n) => date.checked_add_months(Months::new(*n)),
+            Every::Weekdays(days) => date
+                .iter_days()
+                .skip(1)
+                .find(|d| days.contains(&d.weekday())),
+        }
+    }
+
+    /// The date of the occurrence following the one due on `due`, never before `today`.
+    pub fn next_due(&self, due: Option<NaiveDate>, today: NaiveDate) -> Option<NaiveDate> {
+        let mut next = self.next(due.unwrap_or(today))?;
+        while next <= today {
+            next = self.next(next)?;
+        }
+        Some(next)
+    }
+
+    /// The rule the way todo.txt clients write it, `1w` rather than `weekly`.
+    pub fn todotxt(&self) -> String {
+        let strict = if self.strict { "+" } else { "" };
+        match &self.every {
+            Every::Days(n) => format!("{strict}{n}d"),
+            Every::Weeks(n) => format!("{strict}{n}w"),
+            Every::Months(n) if n % 12 == 0 => format!("{strict}{}y", n / 12),
+            Every::Months(n) => format!("{strict}{n}m"),
+            Every::Weekdays(_) => self.to_string(),
+        }
+    }
+}
+
+impl From<Every> for Recurrence {
+    fn from(every: Every) -> Self {
+        Recurrence {
+            every,
+            strict: false,
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Unknown recurrence: '{s}'.");
+
+        let every = match s {
+            "daily" => Every::Days(1),
+            "weekly" => Every::Weeks(1),
+            "monthly" => Every::Months(1),
+            "yearly" => Every::Months(12),
+            "weekdays" => Every::Weekdays(WEEKDAYS[..5].iter().map(|(_, d)| *d).collect()),
+            // todo.txt clients write a '+' for the strict recurrence
+            _ if s.starts_with(|c: char| c.is_ascii_digit() || c == '+') => {
+                let (strict, s) = match s.strip_prefix('+') {
+                    Some(s) => (true, s),
+                    None => (false, s),
+                };
+                let (n, unit) = match s.char_indices().last() {
+                    Some((i, _)) => s.split_at(i),
+                    None => return Err(err()),
+                };
+                let every = match (n.parse::<u32>(), unit) {
+                    (Ok(0), _) | (Err(_), _) => return Err(err()),
+                    (Ok(n), "d") => Every::Days(n),
+                    (Ok(n), "w") => Every::Weeks(n),
+                    (Ok(n), "m") => Every::Months(n),
+                    (Ok(n), "y") => Every::Months(n.checked_mul(12).ok_or_else(err)?),
+                    _ => return Err(err()),
+                };
+                return Ok(Recurrence { every, strict });
+            }
+            _ => {
+                let days = s
+                    .split(',')
+                    .map(|name| WEEKDAYS.iter().find(|(n, _)| *n == name).map(|(_, d)| *d))
+                    .collect::<Option<Vec<Weekday>>>()
+                    .ok_or_else(err)?;
+                Every::Weekdays(days)
+            }
+        };
+        Ok(every.into())
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.strict {
+            return write!(f, "{}", self.todotxt());
+        }
+        match &self.every {
+            Every::Days(1) => write!(f, "daily"),
+            Every::Weeks(1) => write!(f, "weekly"),
+            Every::Months(1) => write!(f, "monthly"),
+            Every::Months(12) => write!(f, "yearly"),
+            Every::Days(n) => write!(f, "{n}d"),
+            Every::Weeks(n) => write!(f, "{n}w"),
+            Every::Months(n) => write!(f, "{n}m"),
+            Every::Weekdays(days) => {
+                let names: Vec<&str> = WEEKDAYS
+                    .iter()
+                    .filter(|(_, d)| days.contains(d))
+                    .map(|(name, _)| *name)
+                    .collect();
+                if names == ["mon", "tue", "wed", "thu", "fri"] {
+                    write!(f, "weekdays")
+                } else {
+                    write!(f, "{}", names.join(","))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn rule(s: &str) -> Recurrence {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(rule("daily"), Every::Days(1).into());
+        assert_eq!(rule("2w"), Every::Weeks(2).into());
+        assert_eq!(
+            rule("+2w"),
+            Recurrence {
+                every: Every::Weeks(2),
+                strict: true
+            }
+        );
+        assert_eq!(rule("6m"), Every::Months(6).into());
+        assert_eq!(rule("2y"), Every::Months(24).into());
+        assert_eq!(
+            rule("mon,thu"),
+            Every::Weekdays(vec![Weekday::Mon, Weekday::Thu]).into()
+        );
+        assert_eq!(rule("weekdays"), rule("mon,tue,wed,thu,fri"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for rule in ["", "+", "0d", "3x", "d", "mon,funday", "4000000000y", "5é"] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{rule}");
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in [
+            "daily", "weekly", "monthly", "yearly", "3d", "2w", "6m", "weekdays", "mon,thu", "+2w",
+            "+1d",
+        ] {
+            assert_eq!(rule(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn todotxt() {
+        assert_eq!(rule("weekly").todotxt(), "1w");
+        assert_eq!(rule("daily").todotxt(), "1d");
+        assert_eq!(rule("monthly").todotxt(), "1m");
+        assert_eq!(rule("yearly").todotxt(), "1y");
+        assert_eq!(rule("18m").todotxt(), "18m");
+        assert_eq!(rule("+1w").todotxt(), "+1w");
+        assert_eq!(rule("+2y").todotxt(), "+2y");
+    }
+
+    #[test]
+    fn next() {
+        // 2024-03-15 is a Friday
+        let fri = day(2024, 3, 15);
+        assert_eq!(rule("3d").next(fri), Some(day(2024, 3, 18)));
+        assert_eq!(rule("2w").next(fri), Some(day(2024, 3, 29)));
+        assert_eq!(
+            rule("monthly").next(day(2024, 1, 31)),
+            Some(day(2024, 2, 29))
+        );
+        assert_eq!(rule("yearly").next(fri), Some(day(2025, 3, 15)));
+
+        let mon_thu = rule("mon,thu");
+        assert_eq!(mon_thu.next(fri), Some(day(2024, 3, 18)));
+        assert_eq!(mon_thu.next(day(2024, 3, 18)), Some(day(2024, 3, 21)));
+    }
+
+    #[test]
+    fn next_due() {
+        let today = day(2024, 3, 15);
+        let weekly = rule("weekly");
+        assert_eq!(weekly.next_due(None, today), Some(day(2024, 3, 22)));
+        assert_eq!(
+            weekly.next_due(Some(day(2024, 3, 14)), today),
+            Some(day(2024, 3, 21))
+        );
+        // Overdue ones skip the missed occurrences
+        assert_eq!(
+            weekly.next_due(Some(day(2024, 2, 1)), today),
+            Some(day(2024, 3, 21))
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let today = day(2024, 3, 15);
+        assert_eq!(rule("4000000000d").next_due(None, today), None);
+        assert_eq!(Recurrence::from(Every::Months(u32::MAX)).next(today), None);
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+pub struct Comment {
+    pub path: String,
+    pub line: usize,
+    pub text: String,
+}
+
+pub struct ScanStats {
+    pub added: usize,
+    pub updated: usize,
+    pub resolved: usize,
+}
+
+/// Normalizes a path so that the same file is linked the same way
+/// no matter how the directory was given.
+pub fn normalize(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
+
+/// Writes the whitespace of a path, and `%` itself, as `%XX` so that the
+/// path stays in a single `src:` token.
+pub fn escape_path(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        if c.is_whitespace() || c == '%' {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("%{byte:02X}"));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Reads back a path written by `escape_path()`.
+pub fn unescape_path(path: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Collects `TODO`/`FIXME` comments from the files under `dir`,
+/// skipping whatever is ignored by `.gitignore` files along the way.
+pub fn scan_dir(dir: &str) -> Result<Vec<Comment>, String> {
+    let re_comment = Regex::new(
+        r"(?://+!?|#+|--+|;+|/\*+|^\s*\*+)\s*(TODO|FIXME)\b(?:\([^)]*\))?:?\s*(.*?)\s*(?:\*/)?\s*$",
+    )
+    .unwrap();
+
+    if !Path::new(dir).is_dir() {
+        return Err(format!("'{dir}' is not a directory."));
+    }
+
+    let mut comments = Vec::new();
+    for entry in WalkBuilder::new(dir).require_git(false).build() {
+        let entry = entry.map_err(|err| err.to_string())?;
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        // Binary and non UTF-8 files can't have comments we could show anyway
+        let Ok(content) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+
+        let path = normalize(entry.path());
+        for (i, line) in content.lines().enumerate() {
+            if let Some(caps) = re_comment.captures(line) {
+                let text = match (&caps[1], &caps[2]) {
+                    ("TODO", "") => "TODO".to_string(),
+                    ("TODO", text) => text.to_string(),
+                    (kind, "") => kind.to_string(),
+                    (kind, text) => format!("{kind}: {text}"),
+                };
+                comments.push(Comment {
+                    path: path.clone(),
+                    line: i + 1,
+                    text,
+                });
+            }
+        }
+    }
+
+    Ok(comments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_paths() {
+        for path in [
+            "src/main.rs",
+            "my dir/a b.rs",
+            "100%.rs",
+            "tab\there",
+            "é à.rs",
+        ] {
+            let escaped = escape_path(path);
+            assert!(!escaped.contains(char::is_whitespace), "{escaped}");
+            assert_eq!(unescape_path(&escaped).as_deref(), Some(path));
+        }
+        assert_eq!(escape_path("my dir/100%.rs"), "my%20dir/100%25.rs");
+
+        for path in ["50%", "%2", "%zz", "%FF"] {
+            assert_eq!(unescape_path(path), None, "{path}");
+        }
+    }
+}
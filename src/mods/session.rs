@@ -0,0 +1,475 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{Days, Local, NaiveDate};
+
+use super::archive::Archive;
+use super::config::Progress;
+use super::format::{Entries, Format};
+use super::stats::Stats;
+use super::todo::{Item, TodoApp};
+use crate::FILE_PATH;
+
+/// The state the lists start in, following the config.
+#[derive(Default)]
+pub struct Startup {
+    pub hide_subtasks: bool,
+    pub start_in_dones: bool,
+    pub columns: Vec<String>,
+}
+
+pub struct Document {
+    pub file_path: String,
+    pub file_name: String,
+    pub format: Format,
+    pub app: TodoApp,
+}
+
+impl Document {
+    fn open(file_path: String, format: Option<Format>, startup: &Startup) -> Result<Self, String> {
+        let format = format.unwrap_or_else(|| Format::from_path(&file_path));
+        let file_name = Path::new(&file_path)
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut app = TodoApp::new();
+        app.parse(&file_path, format)?;
+        if startup.hide_subtasks {
+            app.toggle_subtasks();
+        }
+        // Before the board is set up, which would take it to the next column
+        if startup.start_in_dones {
+            app.toggle_panel();
+        }
+        app.set_columns(&startup.columns);
+
+        Ok(Self {
+            file_path,
+            file_name,
+            format,
+            app,
+        })
+    }
+
+    fn save(&mut self, progress: Progress) -> io::Result<()> {
+        self.app.save(&self.file_path, self.format, progress)
+    }
+
+    /// Moves the DONE items done before `before`, or all of them, to the archive
+    /// of the list. Both files are saved right away to keep them in step.
+    fn archive(&mut self, before: Option<NaiveDate>, progress: Progress) -> io::Result<usize> {
+        let mut archive = Archive::open(&self.file_path)?;
+        let n = self.app.archive(before, |entries| {
+            archive.add(entries);
+            archive.save()
+        })?;
+        if n > 0 {
+            self.save(progress)?;
+        }
+        Ok(n)
+    }
+}
+
+/// The lists at `file_path`, a directory stands for all the `.list` files in it
+/// or a new list when there are none.
+pub fn list_paths(file_path: &str) -> io::Result<Vec<String>> {
+    if !Path::new(file_path).is_dir() {
+        return Ok(vec![file_path.to_string()]);
+    }
+
+    let mut lists: Vec<String> = fs::read_dir(file_path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "list"))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    lists.sort();
+
+    if lists.is_empty() {
+        let new_list = Path::new(file_path).join(FILE_PATH);
+        lists.push(new_list.to_string_lossy().to_string());
+    }
+    Ok(lists)
+}
+
+pub struct Session {
+    docs: Vec<Document>,
+    cur: usize,
+    /// The id and the lists of every move between lists that can be undone.
+    moves: Vec<(usize, usize, usize)>,
+    next_move: usize,
+    register: Entries,
+    progress: Progress,
+    startup: Startup,
+}
+
+impl Session {
+    /// Opens every given file, a directory stands for all the `.list` files in it.
+    pub fn open(
+        file_paths: &[String],
+        format: Option<Format>,
+        startup: Startup,
+    ) -> Result<Self, String> {
+        let mut docs = Vec::new();
+
+        for file_path in file_paths {
+            let lists =
+                list_paths(file_path).map_err(|err| format!("Can't open '{file_path}': {err}"))?;
+            for list in lists {
+                docs.push(Document::open(list, format, &startup)?);
+            }
+        }
+
+        Ok(Self {
+            docs,
+            cur: 0,
+            moves: Vec::new(),
+            next_move: 0,
+            register: Vec::new(),
+            progress: Progress::Count,
+            startup,
+        })
+    }
+
+    /// The progress cookies written to the Markdown and Org files.
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+
+    /// The statistics of the done items of all the open lists, archived ones included.
+    pub fn stats(&self) -> Stats {
+        let archives: Vec<Archive> = self
+            .docs
+            .iter()
+            .filter_map(|doc| Archive::open(&doc.file_path).ok())
+            .collect();
+        let archived = archives
+            .iter()
+            .flat_map(|archive| archive.items().map(|(item, _)| item));
+        let dones = self.docs.iter().flat_map(|doc| doc.app.all_dones());
+        Stats::new(dones.chain(archived), Local::now().date_naive())
+    }
+
+    /// Archives the DONE items of the current list done more than `days` days
+    /// ago, or all of them.
+    pub fn archive(&mut self, days: Option<u64>) {
+        let before = days.map(|days| Local::now().date_naive() - Days::new(days));
+        let progress = self.progress;
+        let doc = &mut self.docs[self.cur];
+        let message = match doc.archive(before, progress) {
+            Ok(0) => "Nothing to archive.".to_string(),
+            Ok(n) => format!("Archived {n} item(s) to '{}.archive'.", doc.file_name),
+            Err(err) => format!("Can't archive: {err}"),
+        };
+        self.app().set_message(&message);
+    }
+
+    /// Archives the DONE items of every list done more than `days` days ago,
+    /// following the `archive_after` option.
+    pub fn archive_old(&mut self, days: u64) {
+        let before = Local::now().date_naive() - Days::new(days);
+        for doc in self.docs.iter_mut() {
+            let message = match doc.archive(Some(before), self.progress) {
+                Ok(0) => continue,
+                Ok(n) => format!("Archived {n} item(s) done more than {days} days ago."),
+                Err(err) => format!("Can't archive: {err}"),
+            };
+            doc.app.set_message(&message);
+        }
+    }
+
+    /// The archive of the current list.
+    pub fn open_archive(&self) -> io::Result<Archive> {
+        Archive::open(&self.doc().file_path)
+    }
+
+    pub fn doc(&self) -> &Document {
+        &self.docs[self.cur]
+    }
+
+    pub fn app(&mut self) -> &mut TodoApp {
+        &mut self.docs[self.cur].app
+    }
+
+    pub fn apps(&mut self) -> impl Iterator<Item = &mut TodoApp> {
+        self.docs.iter_mut().map(|doc| &mut doc.app)
+    }
+
+    pub fn docs(&self) -> impl Iterator<Item = (&Document, bool)> {
+        self.docs
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| (doc, i == self.cur))
+    }
+
+    pub fn docs_n(&self) -> usize {
+        self.docs.len()
+    }
+
+    fn switch_to(&mut self, i: usize) {
+        self.cur = i;
+        let message = format!("Switched to '{}'.", self.doc().file_name);
+        self.app().set_message(&message);
+    }
+
+    pub fn next_list(&mut self) {
+        if self.docs.len() > 1 {
+            self.switch_to((self.cur + 1) % self.docs.len());
+        } else {
+            self.app().set_message("There is only one list.");
+        }
+    }
+
+    pub fn prev_list(&mut self) {
+        if self.docs.len() > 1 {
+            self.switch_to((self.cur + self.docs.len() - 1) % self.docs.len());
+        } else {
+            self.app().set_message("There is only one list.");
+        }
+    }
+
+    /// Moves the current item to the same panel of the next list.
+    pub fn move_item(&mut self) {
+        if self.docs.len() < 2 {
+            self.app()
+                .set_message("Can't move item. There is only one list.");
+            return;
+        }
+
+        let to = (self.cur + 1) % self.docs.len();
+        let to_todos = self.app().is_in_todos();
+        let id = self.next_move;
+        if let Some(entries) = self.app().take_item(id) {
+            self.docs[to].app.put_items(entries, to_todos, id);
+            self.moves.push((id, self.cur, to));
+            self.next_move += 1;
+
+            let message = format!("Moved to '{}'.", self.docs[to].file_name);
+            self.app().set_message(&message);
+        }
+    }
+
+    pub fn get_register(&self) -> &Entries {
+        &self.register
+    }
+
+    /// Copies the selected items or the current one into the register,
+    /// returns true if anything was copied.
+    pub fn yank(&mut self) -> bool {
+        let entries = self.app().yank_items();
+        self.fill_register(entries, "Yanked")
+    }
+
+    pub fn cut(&mut self) -> bool {
+        let entries = self.app().cut_items();
+        self.fill_register(entries, "Cut")
+    }
+
+    fn fill_register(&mut self, entries: Entries, verb: &str) -> bool {
+        if entries.is_empty() {
+            return false;
+        }
+
+        let roots = entries.iter().filter(|(_, level)| *level == 0).count();
+        let message = format!("{verb} {roots} item(s).");
+        self.app().set_message(&message);
+        self.register = entries;
+        true
+    }
+
+    /// Pastes the register after the current item, as siblings or as subtasks.
+    pub fn paste(&mut self, as_child: bool) {
+        if self.register.is_empty() {
+            self.app().set_message("Nothing to paste.");
+        } else {
+            let entries = self.register.clone();
+            self.app().paste_items(entries, as_child);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(id) = self.app().last_move() {
+            let pos = self.moves.iter().position(|&(m, _, _)| m == id);
+
+            if let Some(pos) = pos {
+                let (_, from, to) = self.moves[pos];
+                let other = if from == self.cur { to } else { from };
+
+                // Both halves of a move are undone together or not at all
+                if self.docs[other].app.last_move() != Some(id) {
+                    let message = format!(
+                        "Can't undo the move. '{}' was changed since.",
+                        self.docs[other].file_name
+                    );
+                    self.app().set_message(&message);
+                    return;
+                }
+                self.docs[other].app.undo();
+                self.docs[other].app.clear_message();
+                self.moves.remove(pos);
+            }
+        }
+        self.app().undo();
+    }
+
+    /// Starts the timer of the current item or stops it, one timer runs across all the lists.
+    pub fn toggle_timer(&mut self) {
+        self.app().toggle_timer();
+        if self.app().get_timer().is_some() {
+            let cur = self.cur;
+            for (i, doc) in self.docs.iter_mut().enumerate() {
+                if i != cur {
+                    doc.app.stop_timer();
+                }
+            }
+        }
+    }
+
+    /// The item with the running timer in any of the lists.
+    pub fn get_timer(&self) -> Option<&Item> {
+        self.docs.iter().find_map(|doc| doc.app.get_timer())
+    }
+
+    /// Switches to the list at `file_path`, opening it first if it is not open yet.
+    pub fn edit(&mut self, file_path: &str) {
+        if Path::new(file_path).is_dir() {
+            let message = format!("Can't open '{file_path}'. It is a directory.");
+            self.app().set_message(&message);
+            return;
+        }
+
+        let open = self
+            .docs
+            .iter()
+            .position(|doc| Path::new(&doc.file_path) == Path::new(file_path));
+        match open {
+            Some(i) => self.switch_to(i),
+            None => match Document::open(file_path.to_string(), None, &self.startup) {
+                Ok(doc) => {
+                    self.docs.push(doc);
+                    self.cur = self.docs.len() - 1;
+                }
+                Err(err) => {
+                    let message = format!("Can't open the list: {err}");
+                    self.app().set_message(&message);
+                }
+            },
+        }
+    }
+
+    pub fn save_current(&mut self) {
+        let doc = &mut self.docs[self.cur];
+        let message = match doc.save(self.progress) {
+            Ok(()) => format!("Saved to '{}'.", doc.file_path),
+            Err(err) => format!("Can't save '{}': {err}", doc.file_path),
+        };
+        self.app().set_message(&message);
+    }
+
+    /// Writes the current list to `file_path` in the format of its extension.
+    pub fn export(&mut self, file_path: &str) {
+        let format = Format::from_path(file_path);
+        let progress = self.progress;
+        let message = match self.app().save(file_path, format, progress) {
+            Ok(()) => format!("Exported to '{file_path}' as {format}."),
+            Err(err) => format!("Can't export to '{file_path}': {err}"),
+        };
+        self.app().set_message(&message);
+    }
+
+    pub fn save(&mut self) -> Vec<(String, io::Result<()>)> {
+        self.docs
+            .iter_mut()
+            .map(|doc| (doc.file_path.clone(), doc.save(self.progress)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session on the lists holding `lists`, read from files that are gone afterwards.
+    fn session(name: &str, lists: &[&str]) -> Session {
+        let dir = std::env::temp_dir().join(format!("todors-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut paths = Vec::new();
+        for (i, text) in lists.iter().enumerate() {
+            let path = dir.join(format!("{i}.list"));
+            fs::write(&path, format!("{text}<--->\n")).unwrap();
+            paths.push(path.to_string_lossy().to_string());
+        }
+        let session = Session::open(&paths, None, Startup::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        session
+    }
+
+    fn todos(session: &Session, i: usize) -> Vec<String> {
+        session.docs[i]
+            .app
+            .iter_todos()
+            .map(|(item, level)| format!("{}{}", "  ".repeat(level), item.get_text()))
+            .collect()
+    }
+
+    #[test]
+    fn move_and_undo() {
+        let mut session = session("move", &["TODO(*): a\n    TODO(*): b\nTODO(*): c\n", ""]);
+        session.move_item();
+        assert_eq!(todos(&session, 0), ["c"]);
+        assert_eq!(todos(&session, 1), ["a", "  b"]);
+
+        // Undone from either list, both halves come back
+        session.next_list();
+        session.undo();
+        assert_eq!(todos(&session, 0), ["a", "  b", "c"]);
+        assert_eq!(todos(&session, 1), Vec::<String>::new());
+    }
+
+    #[test]
+    fn undo_move_after_change() {
+        let mut session = session("change", &["TODO(*): a\nTODO(*): b\n", "TODO(*): x\n"]);
+        session.move_item();
+        session.next_list();
+        session.app().mark_item();
+        session.prev_list();
+
+        // The other list was changed since, the move stays
+        session.undo();
+        assert_eq!(todos(&session, 0), ["b"]);
+        assert!(session
+            .app()
+            .get_message()
+            .starts_with("Can't undo the move."));
+
+        session.next_list();
+        session.undo();
+        session.undo();
+        assert_eq!(todos(&session, 0), ["a", "b"]);
+        assert_eq!(todos(&session, 1), ["x"]);
+    }
+
+    #[test]
+    fn moves_are_paired_by_id() {
+        let mut session = session("pair", &["TODO(*): a\nTODO(*): b\n", "", ""]);
+        // a goes to the second list, then b from the second list to the third
+        session.move_item();
+        session.next_list();
+        session.move_item();
+        assert_eq!(todos(&session, 0), ["b"]);
+        assert_eq!(todos(&session, 1), Vec::<String>::new());
+        assert_eq!(todos(&session, 2), ["a"]);
+
+        session.undo();
+        assert_eq!(todos(&session, 1), ["a"]);
+        assert_eq!(todos(&session, 2), Vec::<String>::new());
+        session.undo();
+        assert_eq!(todos(&session, 0), ["a", "b"]);
+        assert_eq!(todos(&session, 1), Vec::<String>::new());
+    }
+}
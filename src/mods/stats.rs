@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Days, NaiveDate};
+
+use super::todo::Item;
+
+/// How many weeks the throughput sparkline covers.
+const WEEKS: usize = 12;
+const DAYS: usize = 7;
+const TOP_TAGS: usize = 5;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Figures about the done items, based on their completion dates.
+pub struct Stats {
+    total: usize,
+    per_day: Vec<(NaiveDate, usize)>,
+    per_week: Vec<usize>,
+    avg_age: Option<f64>,
+    streak: usize,
+    tags: Vec<(String, usize)>,
+}
+
+impl Stats {
+    pub fn new<'a>(dones: impl Iterator<Item = &'a Item>, today: NaiveDate) -> Self {
+        let this_week = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        let first_week = this_week - Days::new(7 * (WEEKS as u64 - 1));
+
+        let mut total = 0;
+        let mut per_date: HashMap<NaiveDate, usize> = HashMap::new();
+        let mut per_week = vec![0; WEEKS];
+        let mut ages = Vec::new();
+        let mut tags: HashMap<String, usize> = HashMap::new();
+
+        for item in dones {
+            let done = item.get_done_date();
+            total += 1;
+            *per_date.entry(done).or_default() += 1;
+            if done >= first_week && done <= today {
+                per_week[(done - first_week).num_days() as usize / 7] += 1;
+            }
+            if let Some(created) = item.get_created() {
+                ages.push((done - created).num_days().max(0) as f64);
+            }
+            for tag in item.tags() {
+                *tags.entry(tag.to_string()).or_default() += 1;
+            }
+        }
+
+        let per_day = (0..DAYS as u64)
+            .rev()
+            .map(|n| today - Days::new(n))
+            .map(|date| (date, per_date.get(&date).copied().unwrap_or(0)))
+            .collect();
+
+        // A streak isn't broken until the day is over
+        let mut day = today;
+        if !per_date.contains_key(&day) {
+            day = day - Days::new(1);
+        }
+        let mut streak = 0;
+        while per_date.contains_key(&day) {
+            streak += 1;
+            day = day - Days::new(1);
+        }
+
+        let avg_age = (!ages.is_empty()).then(|| ages.iter().sum::<f64>() / ages.len() as f64);
+
+        let mut tags: Vec<(String, usize)> = tags.into_iter().collect();
+        tags.sort_by(|(a, n), (b, m)| m.cmp(n).then(a.cmp(b)));
+        tags.truncate(TOP_TAGS);
+
+        Self {
+            total,
+            per_day,
+            per_week,
+            avg_age,
+            streak,
+            tags,
+        }
+    }
+
+    /// The report as lines of text, for the stats screen and the `stats` command.
+    pub fn report(&self) -> Vec<String> {
+        let days: Vec<String> = self
+            .per_day
+            .iter()
+            .map(|(date, n)| format!("{} {n}", date.format("%a")))
+            .collect();
+        let weeks = format!(
+            "{} ({} in total)",
+            sparkline(&self.per_week),
+            self.per_week.iter().sum::<usize>()
+        );
+        let age = self
+            .avg_age
+            .map_or("-".to_string(), |age| format!("{age:.1} days"));
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(tag, n)| format!("{tag} {n}"))
+            .collect();
+
+        [
+            ("Completed", self.total.to_string()),
+            (&format!("Last {DAYS} days"), days.join(", ")),
+            ("This week", self.per_week[WEEKS - 1].to_string()),
+            (&format!("Last {WEEKS} weeks"), weeks),
+            ("Average age when done", age),
+            ("Current streak", format!("{} day(s)", self.streak)),
+            ("Most active tags", tags.join(", ")),
+        ]
+        .into_iter()
+        .map(|(label, value)| format!("{label:<24}{value}"))
+        .collect()
+    }
+}
+
+fn sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .map(|&n| BARS[n * (BARS.len() - 1) / max])
+        .collect()
+}
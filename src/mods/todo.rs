@@ -1,17 +1,41 @@
-use std::cmp::{min, Ordering};
+use std::cmp::{max, min, Ordering};
 use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead, Write};
-use std::process::exit;
+use std::iter;
+use std::path::Path;
+use std::ptr;
+use std::str::FromStr;
+use std::sync::LazyLock;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
 
-use ncurses::constants;
 use regex::Regex;
 
+use super::config::Progress;
+use super::effort::Effort;
+use super::format::{markdown, org, todotxt, Entries, Format};
+use super::query::Query;
+use super::recur::Recurrence;
+use super::scan::{escape_path, normalize, unescape_path, Comment, ScanStats};
+use super::utils::edit_text;
 use crate::INDENT_SIZE;
 const SEP: &str = "<--->";
 const DATE_FMT: &str = "%Y-%m-%d %H:%M %z";
+const DAY_FMT: &str = "%Y-%m-%d";
+const CLOCK_FMT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// A `key:value` or `~estimate` token of the metadata, built once for all the items.
+static RE_META: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s+)([a-z]+:|~)(\S+)").unwrap());
+
+/// The `state:` token of the items in the column `name` of the board.
+pub fn state(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .to_lowercase()
+}
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 enum Panel {
@@ -38,6 +62,16 @@ enum Action {
     Insert,
     Append,
     Edit,
+    /// One half of a move between lists, both halves share the id.
+    Move(usize),
+    Sort,
+    Tag,
+    Indent,
+    Outdent,
+    Cut,
+    Paste,
+    Block,
+    Clock,
     InEdit,
 }
 
@@ -52,6 +86,15 @@ impl fmt::Display for Action {
             Action::Mark => write!(f, "Mark"),
             Action::Append => write!(f, "Append"),
             Action::Edit => write!(f, "Edit"),
+            Action::Move(_) => write!(f, "Move"),
+            Action::Sort => write!(f, "Sort"),
+            Action::Tag => write!(f, "Tag"),
+            Action::Indent => write!(f, "Indent"),
+            Action::Outdent => write!(f, "Outdent"),
+            Action::Cut => write!(f, "Cut"),
+            Action::Paste => write!(f, "Paste"),
+            Action::Block => write!(f, "Block"),
+            Action::Clock => write!(f, "Clock"),
             Action::InEdit => write!(f, ""),
         }
     }
@@ -69,32 +112,231 @@ impl Operation {
     }
 }
 
+/// What the items are sorted by. Items without the date or the priority go
+/// last and the ones with the most subtasks go first.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SortKey {
+    Text,
+    Created,
+    Done,
+    Priority,
+    Due,
+    Subtasks,
+}
+
+impl SortKey {
+    /// Compares the items, `subs` being the number of their subtasks.
+    fn compare(self, (a, subs_a): (&Item, usize), (b, subs_b): (&Item, usize)) -> Ordering {
+        fn none_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        let done = |item: &Item| (!item.is_active()).then_some(item.date);
+
+        match self {
+            SortKey::Text => a.text.to_lowercase().cmp(&b.text.to_lowercase()),
+            SortKey::Created => none_last(a.created, b.created),
+            SortKey::Done => none_last(done(a), done(b)),
+            SortKey::Priority => none_last(a.priority, b.priority),
+            SortKey::Due => none_last(a.due, b.due),
+            SortKey::Subtasks => subs_b.cmp(&subs_a),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SortKey::Text),
+            "created" => Ok(SortKey::Created),
+            "done" => Ok(SortKey::Done),
+            "priority" | "pri" => Ok(SortKey::Priority),
+            "due" => Ok(SortKey::Due),
+            "subtasks" => Ok(SortKey::Subtasks),
+            _ => Err(format!("Unknown sort key: '{s}'.")),
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SortKey::Text => write!(f, "text"),
+            SortKey::Created => write!(f, "creation date"),
+            SortKey::Done => write!(f, "completion date"),
+            SortKey::Priority => write!(f, "priority"),
+            SortKey::Due => write!(f, "due date"),
+            SortKey::Subtasks => write!(f, "subtasks"),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Item {
     text: String,
     date: DateTime<Local>,
+    created: Option<NaiveDate>,
+    priority: Option<char>,
+    source: Option<(String, usize)>,
+    due: Option<NaiveDate>,
+    recur: Option<Recurrence>,
+    uid: Option<u32>,
+    deps: Vec<u32>,
+    clock: Vec<(DateTime<Local>, Option<DateTime<Local>>)>,
+    estimate: Option<Effort>,
+    state: Option<String>,
+    notes: Vec<String>,
+    heading: Option<usize>,
+    pos: Option<usize>,
     parent: Option<usize>,
     children: Vec<usize>,
     act_cnt: usize,
+    selected: bool,
+    folded: bool,
 }
 
 impl Item {
-    fn new(text: String, date: DateTime<Local>, parent: Option<usize>, act_cnt: usize) -> Self {
+    pub fn new(text: String, date: DateTime<Local>, parent: Option<usize>, act_cnt: usize) -> Self {
         Self {
             text,
             date,
+            created: None,
+            priority: None,
+            source: None,
+            due: None,
+            recur: None,
+            uid: None,
+            deps: Vec::new(),
+            clock: Vec::new(),
+            estimate: None,
+            state: None,
+            notes: Vec::new(),
+            heading: None,
+            pos: None,
             parent,
             children: Vec::new(),
             act_cnt,
+            selected: false,
+            folded: false,
         }
     }
 
+    fn new_todo(parent: Option<usize>) -> Self {
+        let mut item = Self::new(String::new(), Local::now(), parent, 1);
+        item.created = Some(Local::now().date_naive());
+        item
+    }
+
     pub fn get_text(&self) -> &String {
         &self.text
     }
 
-    pub fn get_date(&self) -> String {
-        self.date.format("%y-%m-%d").to_string()
+    pub fn get_date(&self, fmt: &str) -> String {
+        self.date.format(fmt).to_string()
+    }
+
+    pub fn get_datetime(&self) -> DateTime<Local> {
+        self.date
+    }
+
+    pub fn get_done_date(&self) -> NaiveDate {
+        self.date.date_naive()
+    }
+
+    pub fn get_created(&self) -> Option<NaiveDate> {
+        self.created
+    }
+
+    pub fn get_priority(&self) -> Option<char> {
+        self.priority
+    }
+
+    pub fn get_source(&self) -> Option<(&String, usize)> {
+        self.source.as_ref().map(|(path, line)| (path, *line))
+    }
+
+    pub fn get_due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    /// Tells whether the item is due on `day` or was done on it.
+    pub fn is_on(&self, day: NaiveDate) -> bool {
+        self.due == Some(day) || !self.is_active() && self.get_done_date() == day
+    }
+
+    pub fn get_recurrence(&self) -> Option<&Recurrence> {
+        self.recur.as_ref()
+    }
+
+    pub fn get_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    pub fn get_deps(&self) -> &[u32] {
+        &self.deps
+    }
+
+    /// The lines of a Markdown or Org file following the item that are not
+    /// items themselves, written back after it as they were.
+    pub fn get_notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn add_note(&mut self, line: String) {
+        self.notes.push(line);
+    }
+
+    /// The number of `#` of an item read from a Markdown heading, or of `*`
+    /// of an Org headline without a TODO/DONE keyword.
+    pub fn get_heading(&self) -> Option<usize> {
+        self.heading
+    }
+
+    pub fn set_heading(&mut self, heading: Option<usize>) {
+        self.heading = heading;
+    }
+
+    /// The position of a root item among the roots of the file it was read from.
+    pub fn get_pos(&self) -> Option<usize> {
+        self.pos
+    }
+
+    /// The start of the running timer of the item.
+    pub fn clocked_since(&self) -> Option<DateTime<Local>> {
+        match self.clock.last() {
+            Some((start, None)) => Some(*start),
+            _ => None,
+        }
+    }
+
+    /// The time tracked on the item, the running timer counts until `now`.
+    pub fn tracked(&self, now: DateTime<Local>) -> Duration {
+        self.clock
+            .iter()
+            .map(|(start, end)| end.unwrap_or(now) - *start)
+            .sum()
+    }
+
+    fn clock_in(&mut self) {
+        if self.clocked_since().is_none() {
+            self.clock.push((Local::now(), None));
+        }
+    }
+
+    fn clock_out(&mut self) {
+        self.clock_out_at(Local::now());
+    }
+
+    fn clock_out_at(&mut self, time: DateTime<Local>) {
+        if let Some((_, end @ None)) = self.clock.last_mut() {
+            *end = Some(time);
+        }
     }
 
     pub fn is_active(&self) -> bool {
@@ -109,9 +351,202 @@ impl Item {
         self.parent.is_none()
     }
 
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    pub fn is_folded(&self) -> bool {
+        self.folded && self.has_children()
+    }
+
+    /// Tags are the `+tag`, `@tag` and `#tag` words of the text.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.text
+            .split_whitespace()
+            .filter(|word| word.len() > 1 && word.starts_with(['+', '@', '#']))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().any(|word| word[1..] == *tag)
+    }
+
     fn trim_text(&mut self) {
         self.text = self.text.trim().to_string();
     }
+
+    /// Moves the known `key:value` and `~estimate` tokens out of the text into their fields.
+    pub fn parse_meta(&mut self) {
+        let text = self.text.clone();
+        let mut stripped = String::new();
+        let mut last = 0;
+
+        for caps in RE_META.captures_iter(&text) {
+            if self.set_meta(caps[1].trim_end_matches(':'), &caps[2]) {
+                let m = caps.get(0).unwrap();
+                stripped.push_str(&text[last..m.start()]);
+                last = m.end();
+            }
+        }
+        stripped.push_str(&text[last..]);
+
+        self.text = stripped.trim().to_string();
+    }
+
+    fn set_meta(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "pri" => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(pri @ 'A'..='Z'), None) => self.priority = Some(pri),
+                    _ => return false,
+                }
+            }
+            "created" => match NaiveDate::parse_from_str(value, DAY_FMT) {
+                Ok(date) => self.created = Some(date),
+                Err(_) => return false,
+            },
+            "src" => match value.rsplit_once(':') {
+                Some((path, line)) if !path.is_empty() => {
+                    match (unescape_path(path), line.parse()) {
+                        (Some(path), Ok(line)) => self.source = Some((path, line)),
+                        _ => return false,
+                    }
+                }
+                _ => return false,
+            },
+            "due" => match NaiveDate::parse_from_str(value, DAY_FMT) {
+                Ok(date) => self.due = Some(date),
+                Err(_) => return false,
+            },
+            "rec" => match value.parse() {
+                Ok(rule) => self.recur = Some(rule),
+                Err(_) => return false,
+            },
+            "state" => self.state = Some(value.to_string()),
+            "uid" => match value.parse() {
+                Ok(uid) => self.uid = Some(uid),
+                Err(_) => return false,
+            },
+            "dep" => match value
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<u32>, _>>()
+            {
+                Ok(deps) => {
+                    for dep in deps {
+                        if !self.deps.contains(&dep) {
+                            self.deps.push(dep);
+                        }
+                    }
+                }
+                Err(_) => return false,
+            },
+            "clock" => {
+                let parse = |time: &str| {
+                    NaiveDateTime::parse_from_str(time, CLOCK_FMT)
+                        .ok()
+                        .and_then(|time| time.and_local_timezone(Local).earliest())
+                };
+                match value.split_once('/') {
+                    Some((start, "")) => match parse(start) {
+                        Some(start) => self.clock.push((start, None)),
+                        None => return false,
+                    },
+                    Some((start, end)) => match (parse(start), parse(end)) {
+                        (Some(start), Some(end)) => self.clock.push((start, Some(end))),
+                        _ => return false,
+                    },
+                    None => return false,
+                }
+            }
+            "~" => match value.parse() {
+                Ok(effort) => self
+                    .estimate
+                    .get_or_insert_with(Effort::default)
+                    .update(effort),
+                Err(_) => return false,
+            },
+            "fold" if value == "closed" => self.folded = true,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The fields that are saved as `key:value` tokens after the text.
+    pub fn meta(&self) -> Vec<String> {
+        let mut meta = Vec::new();
+        if let Some(pri) = self.priority {
+            meta.push(format!("pri:{pri}"));
+        }
+        if let Some(created) = self.created {
+            meta.push(format!("created:{}", created.format(DAY_FMT)));
+        }
+        if let Some((path, line)) = &self.source {
+            meta.push(format!("src:{}:{line}", escape_path(path)));
+        }
+        if let Some(due) = self.due {
+            meta.push(format!("due:{}", due.format(DAY_FMT)));
+        }
+        if let Some(rule) = &self.recur {
+            meta.push(format!("rec:{rule}"));
+        }
+        if let Some(state) = &self.state {
+            meta.push(format!("state:{state}"));
+        }
+        if let Some(uid) = self.uid {
+            meta.push(format!("uid:{uid}"));
+        }
+        if !self.deps.is_empty() {
+            let deps: Vec<String> = self.deps.iter().map(u32::to_string).collect();
+            meta.push(format!("dep:{}", deps.join(",")));
+        }
+        if let Some(estimate) = self.estimate {
+            meta.push(estimate.to_string());
+        }
+        for (start, end) in &self.clock {
+            let end = end.map_or(String::new(), |end| end.format(CLOCK_FMT).to_string());
+            meta.push(format!("clock:{}/{end}", start.format(CLOCK_FMT)));
+        }
+        if self.is_folded() {
+            meta.push("fold:closed".to_string());
+        }
+        meta
+    }
+
+    /// Reads a `DONE(date): text` line of the list format, without the indentation.
+    pub fn from_done_line(line: &str) -> Result<Self, &'static str> {
+        let re_done = Regex::new(r"^\s*DONE\((.*)\): (.*)$").unwrap();
+
+        if let Some(caps) = re_done.captures(line) {
+            let date = DateTime::parse_from_str(&caps[1], DATE_FMT);
+            if let Ok(d) = date {
+                let mut item = Item::new(caps[2].trim().to_string(), d.into(), None, 0);
+                item.parse_meta();
+                Ok(item)
+            } else {
+                Err("invalid date format for a DONE item")
+            }
+        } else {
+            Err("invalid format for a DONE item")
+        }
+    }
+
+    /// The line of the item in the DONE section of the list format.
+    pub fn done_line(&self, level: usize) -> String {
+        let indent = " ".repeat(level * INDENT_SIZE);
+        let date = self.date.format(DATE_FMT);
+        format!("{indent}DONE({date}): {}", self.text_with_meta())
+    }
+
+    /// The text along with the tokens of the metadata, as it is saved.
+    pub fn text_with_meta(&self) -> String {
+        let mut line = self.text.clone();
+        for token in self.meta() {
+            line.push(' ');
+            line.push_str(&token);
+        }
+        line
+    }
 }
 
 #[derive(Debug)]
@@ -119,12 +554,20 @@ struct List {
     state_stack: Vec<(Vec<Item>, usize)>,
     cur: usize,
     list: Vec<Item>,
+    filter: Option<Query>,
+    day: Option<NaiveDate>,
+    unblocked: bool,
+    columns: Vec<String>,
+    column: usize,
+    pin_cur: bool,
+    visual: Option<(usize, bool)>,
+    visual_base: Vec<bool>,
 }
 
 pub struct ListIter<'a> {
     obj: &'a List,
     cur: usize,
-    skip_children: bool,
+    shown: Vec<bool>,
 }
 
 impl<'a> Iterator for ListIter<'a> {
@@ -135,18 +578,11 @@ impl<'a> Iterator for ListIter<'a> {
             let item = &self.obj.list[self.cur];
             self.cur += 1;
 
-            if self.skip_children && item.parent.is_some() {
+            if !self.shown[self.cur - 1] {
                 return self.next();
             }
 
-            let mut level = 0;
-            let mut parent = item.parent;
-            while let Some(p) = parent {
-                level += 1;
-                parent = self.obj.list[p].parent;
-            }
-
-            Some((item, level))
+            Some((item, self.obj.depth(self.cur - 1)))
         } else {
             None
         }
@@ -159,6 +595,286 @@ impl List {
             state_stack: Vec::new(),
             cur: 0,
             list: Vec::new(),
+            filter: None,
+            day: None,
+            unblocked: false,
+            columns: Vec::new(),
+            column: 0,
+            pin_cur: false,
+            visual: None,
+            visual_base: Vec::new(),
+        }
+    }
+
+    fn from_levels(entries: Entries) -> Self {
+        let mut list = Self::new();
+        let mut stack: Vec<usize> = Vec::new();
+
+        for (i, (mut item, level)) in entries.into_iter().enumerate() {
+            stack.truncate(level);
+            item.parent = stack.last().copied();
+            item.children.clear();
+
+            list.add_item(item);
+            if let Some(parent) = stack.last() {
+                list.add_child_to(*parent, i, false);
+            }
+            stack.push(i);
+        }
+
+        list.fix_counts();
+        list
+    }
+
+    fn fix_counts(&mut self) {
+        // A parent can't be done while any of its subtasks is still active
+        for i in (0..self.list.len()).rev() {
+            if let Some(p) = self.list[i].parent {
+                if self.list[i].is_active() && !self.list[p].is_active() {
+                    self.list[p].act_cnt = 1;
+                }
+            }
+        }
+
+        for i in 0..self.list.len() {
+            if self.list[i].is_active() {
+                let active_children = self.list[i]
+                    .children
+                    .iter()
+                    .filter(|&&c| self.list[c].is_active())
+                    .count();
+                self.list[i].act_cnt = 1 + active_children;
+            }
+        }
+    }
+
+    fn iter(&self, skip_children: bool) -> ListIter<'_> {
+        ListIter {
+            obj: self,
+            cur: 0,
+            shown: self.visible(!skip_children),
+        }
+    }
+
+    fn iter_column(&self, column: usize, skip_children: bool) -> ListIter<'_> {
+        ListIter {
+            obj: self,
+            cur: 0,
+            shown: self.visible_in(column, !skip_children),
+        }
+    }
+
+    /// Iterates over the items matching the filter, folded ones included.
+    fn iter_matching(&self) -> ListIter<'_> {
+        ListIter {
+            obj: self,
+            cur: 0,
+            shown: self.matching(),
+        }
+    }
+
+    /// Iterates over every item regardless of the filter.
+    fn iter_all(&self) -> ListIter<'_> {
+        ListIter {
+            obj: self,
+            cur: 0,
+            shown: vec![true; self.list.len()],
+        }
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.filter.is_some() || self.day.is_some()
+    }
+
+    /// Tells whether the item matches the query of the filter and is due or was
+    /// done on the day of the filter.
+    fn matches(&self, item: &Item, today: NaiveDate) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|query| query.matches(item, today))
+            && self.day.is_none_or(|day| item.is_on(day))
+    }
+
+    /// The state of the items in `column`, the first column has none so that
+    /// lists without columns start there.
+    fn column_state(&self, column: usize) -> Option<String> {
+        self.columns.get(column).filter(|_| column > 0).cloned()
+    }
+
+    /// The column of every item, given by the state of its root. Items with an
+    /// unknown state go to the first column.
+    fn item_columns(&self) -> Vec<usize> {
+        let mut columns = vec![0; self.list.len()];
+        for i in 0..self.list.len() {
+            columns[i] = match self.list[i].parent {
+                Some(p) => columns[p],
+                None => self.list[i]
+                    .state
+                    .as_ref()
+                    .and_then(|state| self.columns.iter().position(|c| c == state))
+                    .unwrap_or(0),
+            };
+        }
+        columns
+    }
+
+    /// Moves the current root item `by` columns, it can't leave the columns of the board.
+    fn shift_column(&mut self, by: isize) -> Result<(), &'static str> {
+        let Some(item) = self.get_cur_item() else {
+            return Err("Can't move item. List is empty.");
+        };
+        if !item.is_root() {
+            return Err("Can't move item. Item is a subtask.");
+        }
+
+        let to = self.item_columns()[self.cur] as isize + by;
+        if to < 0 {
+            return Err("Can't move item back. Item is in the first column.");
+        } else if to as usize >= self.columns.len() {
+            return Err("Can't transfer item. Item is still active.");
+        }
+        self.list[self.cur].state = self.column_state(to as usize);
+        Ok(())
+    }
+
+    /// Keeps the current item in the column on the screen once it is a root item.
+    fn settle(&mut self) {
+        if self.columns.is_empty() {
+            return;
+        }
+        let state = self.column_state(self.column);
+        if let Some(item) = self.list.get_mut(self.cur).filter(|item| item.is_root()) {
+            item.state = state;
+        }
+    }
+
+    /// Tells which items match the filter along with their parents and subtasks.
+    fn matching(&self) -> Vec<bool> {
+        let mut shown = vec![true; self.list.len()];
+
+        if self.is_filtered() {
+            let today = Local::now().date_naive();
+            let mut matched: Vec<bool> = self
+                .list
+                .iter()
+                .map(|item| self.matches(item, today))
+                .collect();
+
+            // Parents always come before their subtasks
+            shown.copy_from_slice(&matched);
+            for i in (0..self.list.len()).rev() {
+                if let Some(p) = self.list[i].parent {
+                    shown[p] |= shown[i];
+                }
+            }
+            for i in 0..self.list.len() {
+                if let Some(p) = self.list[i].parent {
+                    matched[i] |= matched[p];
+                }
+                shown[i] |= matched[i];
+            }
+        }
+        shown
+    }
+
+    fn visible(&self, full: bool) -> Vec<bool> {
+        self.visible_in(self.column, full)
+    }
+
+    /// Tells which items are shown: the ones matching the filter along with their
+    /// parents and subtasks, only the roots of them unless `full`. The subtasks
+    /// of folded items are never shown. In the unblocked view only the items that
+    /// can be marked right away are kept, with their parents. On a board only the
+    /// items of `column` are shown.
+    fn visible_in(&self, column: usize, full: bool) -> Vec<bool> {
+        let mut shown = self.matching();
+
+        if self.unblocked {
+            let mut ready: Vec<bool> = self
+                .list
+                .iter()
+                .map(|item| item.act_cnt == 1 && !self.is_blocked(item))
+                .collect();
+            for i in (0..self.list.len()).rev() {
+                if let Some(p) = self.list[i].parent {
+                    ready[p] |= ready[i];
+                }
+            }
+            for (s, ready) in shown.iter_mut().zip(ready) {
+                *s &= ready;
+            }
+        }
+
+        if !self.columns.is_empty() {
+            for (s, c) in shown.iter_mut().zip(self.item_columns()) {
+                *s &= c == column;
+            }
+        }
+
+        if !full {
+            for (s, item) in shown.iter_mut().zip(&self.list) {
+                *s &= item.is_root();
+            }
+        }
+        let mut in_fold = vec![false; self.list.len()];
+        for i in 0..self.list.len() {
+            if let Some(p) = self.list[i].parent {
+                in_fold[i] = in_fold[p] || self.list[p].folded;
+                shown[i] &= !in_fold[i];
+            }
+        }
+        if self.pin_cur && column == self.column {
+            if let Some(s) = shown.get_mut(self.cur) {
+                *s = true;
+            }
+        }
+
+        shown
+    }
+
+    fn is_shown(&self, i: usize) -> bool {
+        !self.is_filtered() && !self.unblocked && self.columns.is_empty()
+            || self.pin_cur
+            || self.visible(true).get(i) == Some(&true)
+    }
+
+    /// An item is blocked while any of the items it depends on is still active.
+    fn is_blocked(&self, item: &Item) -> bool {
+        item.deps.iter().any(|&dep| {
+            self.list
+                .iter()
+                .any(|other| other.uid == Some(dep) && other.is_active())
+        })
+    }
+
+    /// Tells whether `from` depends on the item `uid`, directly or through other items.
+    fn depends_on(&self, from: &Item, uid: u32) -> bool {
+        let mut seen = Vec::new();
+        let mut stack = from.deps.clone();
+        while let Some(dep) = stack.pop() {
+            if dep == uid {
+                return true;
+            }
+            if seen.contains(&dep) {
+                continue;
+            }
+            seen.push(dep);
+            if let Some(item) = self.list.iter().find(|item| item.uid == Some(dep)) {
+                stack.extend(&item.deps);
+            }
+        }
+        false
+    }
+
+    /// Moves the cursor off a hidden item, to the closest shown one above if any.
+    fn fix_cursor(&mut self, full: bool) {
+        let shown = self.visible(full);
+        if shown.get(self.cur) == Some(&false) {
+            if let Some(prev) = shown[..self.cur].iter().rposition(|&s| s) {
+                self.cur = prev;
+            } else if let Some(next) = shown[self.cur..].iter().position(|&s| s) {
+                self.cur += next;
+            }
         }
     }
 
@@ -183,12 +899,36 @@ impl List {
         }
     }
 
+    /// The unseen item of the comment with the `text` it reads as, the one still
+    /// on the line of the comment first when the text is there several times.
+    fn find_source(&self, comment: &Comment, text: &str, seen: &[bool]) -> Option<usize> {
+        (0..self.list.len())
+            .filter(|&i| {
+                !seen[i]
+                    && self.list[i].text == text
+                    && self.list[i]
+                        .source
+                        .as_ref()
+                        .is_some_and(|(path, _)| *path == comment.path)
+            })
+            .min_by_key(|&i| {
+                self.list[i]
+                    .source
+                    .as_ref()
+                    .is_some_and(|(_, line)| *line != comment.line)
+            })
+    }
+
     fn get_cur_item(&self) -> Option<&Item> {
-        self.list.get(self.cur)
+        self.list.get(self.cur).filter(|_| self.is_shown(self.cur))
     }
 
     fn get_cur_item_mut(&mut self) -> Option<&mut Item> {
-        self.list.get_mut(self.cur)
+        if self.is_shown(self.cur) {
+            self.list.get_mut(self.cur)
+        } else {
+            None
+        }
     }
 
     fn record_state(&mut self) {
@@ -205,37 +945,21 @@ impl List {
     }
 
     fn up(&mut self, full: bool) {
-        if self.cur > 0 && !self.list.is_empty() {
-            if full {
-                self.cur -= 1;
-            } else {
-                if let Some(next) = self.list[..self.cur]
-                    .iter()
-                    .rposition(|item| item.is_root())
-                {
-                    self.cur = next;
-                }
-            }
+        let shown = self.visible(full);
+        if let Some(prev) = shown[..min(self.cur, shown.len())].iter().rposition(|&s| s) {
+            self.cur = prev;
         }
     }
 
     fn down(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = min(self.cur + 1, self.list.len() - 1)
-            } else {
-                if let Some(next) = self.list[self.cur + 1..]
-                    .iter()
-                    .position(|item| item.is_root())
-                {
-                    self.cur += next + 1;
-                }
-            }
+        let shown = self.visible(full);
+        if let Some(next) = shown.iter().skip(self.cur + 1).position(|&s| s) {
+            self.cur += next + 1;
         }
     }
 
     fn drag_up(&mut self) -> Result<(), &'static str> {
-        if let Some(item) = self.list.get_mut(self.cur) {
+        if let Some(item) = self.get_cur_item_mut() {
             let parent = item.parent;
             let pier = self.list[..self.cur]
                 .iter()
@@ -279,96 +1003,430 @@ impl List {
         }
     }
 
-    fn drag_down(&mut self) -> Result<(), &'static str> {
-        if let Some(item) = self.list.get_mut(self.cur) {
-            let parent = item.parent;
-            let pier = self.list[self.cur + 1..]
-                .iter()
-                .position(|item| item.parent == parent);
+    fn drag_down(&mut self) -> Result<(), &'static str> {
+        if let Some(item) = self.get_cur_item_mut() {
+            let parent = item.parent;
+            let pier = self.list[self.cur + 1..]
+                .iter()
+                .position(|item| item.parent == parent);
+
+            match (parent, pier) {
+                (None, None) => Err("Can't drag down. Item is already at the bottom."),
+                (Some(_), None) => Err("Can't move a subtask out from its parent."),
+                (_, Some(pier)) => {
+                    let pier = pier + self.cur + 1;
+                    let pier_child_cnt = self.children_cnt(pier) + 1;
+                    let child_cnt = self.children_cnt(self.cur) + 1;
+                    let move_to = self.cur + pier_child_cnt;
+
+                    self.shift_indices(pier_child_cnt as isize, self.cur, Some(pier), parent);
+                    self.shift_indices(
+                        -(child_cnt as isize),
+                        pier,
+                        Some(pier + pier_child_cnt),
+                        self.list[pier].parent,
+                    );
+
+                    let to_drag: Vec<Item> =
+                        self.list.drain(self.cur..self.cur + child_cnt).collect();
+                    self.list.splice(move_to..move_to, to_drag);
+
+                    if let Some(parent) = parent {
+                        self.list[parent].children.retain(|&x| x != pier);
+                        self.list[parent].children.push(move_to);
+                    }
+
+                    self.cur = move_to;
+                    Ok(())
+                }
+            }
+        } else {
+            Err("Can't drag down. List is empty.")
+        }
+    }
+
+    /// Repeats the action `count` times, stopping early once it fails.
+    /// Fails only if the action could not be done even once.
+    fn repeat(
+        &mut self,
+        count: usize,
+        action: fn(&mut List) -> Result<(), &'static str>,
+    ) -> Result<(), &'static str> {
+        for i in 0..count {
+            if let Err(err) = action(self) {
+                if i == 0 {
+                    return Err(err);
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn first(&mut self, full: bool) {
+        if let Some(first) = self.visible(full).iter().position(|&s| s) {
+            self.cur = first;
+        }
+    }
+
+    fn half(&mut self, full: bool) {
+        let shown: Vec<usize> = (0..self.list.len())
+            .zip(self.visible(full))
+            .filter_map(|(i, s)| s.then_some(i))
+            .collect();
+        if let Some(&half) = shown.get(shown.len() / 2) {
+            self.cur = half;
+        }
+    }
+
+    fn last(&mut self, full: bool) {
+        if let Some(last) = self.visible(full).iter().rposition(|&s| s) {
+            self.cur = last;
+        }
+    }
+
+    /// Puts the cursor on the `n`th shown item, counting from 1.
+    fn go_to(&mut self, n: usize, full: bool) -> Result<(), &'static str> {
+        let shown = self.visible(full);
+        match (0..self.list.len())
+            .filter(|&i| shown[i])
+            .nth(n.wrapping_sub(1))
+        {
+            Some(i) => {
+                self.cur = i;
+                Ok(())
+            }
+            None => Err("No such item."),
+        }
+    }
+
+    fn children_cnt(&self, parent: usize) -> usize {
+        let mut cnt = 0;
+        if let Some(item) = self.list.get(parent) {
+            if item.children.is_empty() {
+                return 0;
+            }
+
+            for child in item.children.iter() {
+                cnt += 1 + self.children_cnt(*child);
+            }
+        }
+        cnt
+    }
+
+    fn depth(&self, i: usize) -> usize {
+        let mut depth = 0;
+        let mut parent = self.list[i].parent;
+        while let Some(p) = parent {
+            depth += 1;
+            parent = self.list[p].parent;
+        }
+        depth
+    }
+
+    fn subtree(&self, i: usize) -> Entries {
+        let base = self.depth(i);
+        (i..=i + self.children_cnt(i))
+            .map(|j| (self.list[j].clone(), self.depth(j) - base))
+            .collect()
+    }
+
+    fn entries(&self) -> Entries {
+        self.iter_all()
+            .map(|(item, level)| (item.clone(), level))
+            .collect()
+    }
+
+    fn rebuild(&mut self, entries: Entries) {
+        self.list = List::from_levels(entries).list;
+        self.cur = min(self.cur, self.list.len().saturating_sub(1));
+    }
+
+    /// Makes the current item the last subtask of its previous sibling.
+    fn indent(&mut self) -> Result<(), &'static str> {
+        let parent = self
+            .get_cur_item()
+            .ok_or("Can't indent item. List is empty.")?
+            .parent;
+        if !self.list[..self.cur]
+            .iter()
+            .any(|item| item.parent == parent)
+        {
+            return Err("Can't indent item. There is no item above to put it under.");
+        }
+
+        let cur = self.cur;
+        let last = cur + self.children_cnt(cur);
+        let mut entries = self.entries();
+        for (_, level) in entries[cur..=last].iter_mut() {
+            *level += 1;
+        }
+        self.rebuild(entries);
+        self.cur = cur;
+
+        Ok(())
+    }
+
+    /// Makes the current item the next sibling of its parent.
+    fn outdent(&mut self) -> Result<(), &'static str> {
+        let parent = self
+            .get_cur_item()
+            .ok_or("Can't outdent item. List is empty.")?
+            .parent
+            .ok_or("Can't outdent item. Item is already a root.")?;
+
+        let len = self.children_cnt(self.cur) + 1;
+        let parent_end = parent + self.children_cnt(parent) + 1;
+        let mut entries = self.entries();
+        let mut moved: Entries = entries.drain(self.cur..self.cur + len).collect();
+        for (_, level) in moved.iter_mut() {
+            *level -= 1;
+        }
+
+        let to = parent_end - len;
+        entries.splice(to..to, moved);
+        self.rebuild(entries);
+        self.cur = to;
+
+        Ok(())
+    }
+
+    /// Copies the selected items or the current one, along with their subtasks.
+    fn yank(&mut self) -> Entries {
+        let cur = self.cur;
+        let mut entries = Vec::new();
+
+        self.for_selected(false, false, |list| {
+            if list.get_cur_item().is_some() {
+                entries.append(&mut list.subtree(list.cur));
+            }
+            Ok(())
+        })
+        .unwrap();
+        self.cur = cur;
+
+        entries
+    }
+
+    /// Inserts the items right after the current one and its subtasks,
+    /// as its siblings or as its last subtasks.
+    fn paste(&mut self, mut items: Entries, as_child: bool) -> Result<(), &'static str> {
+        let (to, base) = match self.get_cur_item() {
+            Some(_) => (
+                self.cur + self.children_cnt(self.cur) + 1,
+                self.depth(self.cur) + usize::from(as_child),
+            ),
+            None if as_child => return Err("Can't paste as subtasks. List is empty."),
+            None => (self.list.len(), 0),
+        };
+
+        let state = self.column_state(self.column);
+        for (item, level) in items.iter_mut() {
+            *level += base;
+            if *level == 0 && !self.columns.is_empty() {
+                item.state = state.clone();
+            }
+        }
+        let mut entries = self.entries();
+        entries.splice(to..to, items);
+        self.rebuild(entries);
+        self.cur = to;
+
+        Ok(())
+    }
+
+    /// Sorts the root items by `key` with their subtasks attached, and the
+    /// subtasks of every item too if `deep`. The cursor stays on the same item.
+    fn sort(&mut self, key: SortKey, deep: bool) -> Result<(), &'static str> {
+        if self.list.is_empty() {
+            return Err("Can't sort. List is empty.");
+        }
+
+        let levels: Vec<usize> = (0..self.list.len()).map(|i| self.depth(i)).collect();
+        let order = self.sorted((0..self.list.len()).collect(), &levels, key, deep);
+
+        let entries = order
+            .iter()
+            .map(|&i| (self.list[i].clone(), levels[i]))
+            .collect();
+        self.cur = order.iter().position(|&i| i == self.cur).unwrap();
+        self.rebuild(entries);
+
+        Ok(())
+    }
+
+    /// Sorts the trees made of `indices`, the first one being at the top level of them.
+    fn sorted(
+        &self,
+        indices: Vec<usize>,
+        levels: &[usize],
+        key: SortKey,
+        deep: bool,
+    ) -> Vec<usize> {
+        let level = levels[indices[0]];
+        let mut trees: Vec<Vec<usize>> = Vec::new();
+        for i in indices {
+            match trees.last_mut() {
+                Some(tree) if levels[i] > level => tree.push(i),
+                _ => trees.push(vec![i]),
+            }
+        }
+
+        // The sort is stable, so equal items keep their order
+        trees.sort_by(|a, b| {
+            key.compare(
+                (&self.list[a[0]], a.len() - 1),
+                (&self.list[b[0]], b.len() - 1),
+            )
+        });
+
+        let mut order = Vec::new();
+        for mut tree in trees {
+            order.push(tree[0]);
+            let subtasks = tree.split_off(1);
+            if deep && !subtasks.is_empty() {
+                order.extend(self.sorted(subtasks, levels, key, deep));
+            } else {
+                order.extend(subtasks);
+            }
+        }
+        order
+    }
+
+    /// Starts selecting the items between the cursor and where it is moved to,
+    /// along with all of their subtasks if `subtrees`.
+    fn start_visual(&mut self, subtrees: bool) {
+        self.visual = Some((self.cur, subtrees));
+        self.visual_base = self.list.iter().map(|item| item.selected).collect();
+    }
 
-            match (parent, pier) {
-                (None, None) => Err("Can't drag down. Item is already at the bottom."),
-                (Some(_), None) => Err("Can't move a subtask out from its parent."),
-                (_, Some(pier)) => {
-                    let pier = pier + self.cur + 1;
-                    let pier_child_cnt = self.children_cnt(pier) + 1;
-                    let child_cnt = self.children_cnt(self.cur) + 1;
-                    let move_to = self.cur + pier_child_cnt;
+    /// Leaves the visual mode, the items in the range stay selected.
+    fn end_visual(&mut self) -> bool {
+        self.visual_base.clear();
+        self.visual.take().is_some()
+    }
 
-                    self.shift_indices(pier_child_cnt as isize, self.cur, Some(pier), parent);
-                    self.shift_indices(
-                        -(child_cnt as isize),
-                        pier,
-                        Some(pier + pier_child_cnt),
-                        self.list[pier].parent,
-                    );
+    fn update_visual(&mut self, full: bool) {
+        if let Some((anchor, subtrees)) = self.visual {
+            if self.visual_base.len() != self.list.len() {
+                self.end_visual();
+                return;
+            }
 
-                    let to_drag: Vec<Item> =
-                        self.list.drain(self.cur..self.cur + child_cnt).collect();
-                    self.list.splice(move_to..move_to, to_drag);
+            let (from, to) = (min(anchor, self.cur), max(anchor, self.cur));
+            let in_range: Vec<usize> = (0..self.list.len())
+                .zip(self.visible(full))
+                .filter_map(|(i, shown)| (from <= i && i <= to && shown).then_some(i))
+                .collect();
 
-                    if let Some(parent) = parent {
-                        self.list[parent].children.retain(|&x| x != pier);
-                        self.list[parent].children.push(move_to);
-                    }
+            for (item, &base) in self.list.iter_mut().zip(&self.visual_base) {
+                item.selected = base;
+            }
+            for i in in_range {
+                let last = if subtrees {
+                    i + self.children_cnt(i)
+                } else {
+                    i
+                };
+                self.list[i..=last]
+                    .iter_mut()
+                    .for_each(|item| item.selected = true);
+            }
+        }
+    }
 
-                    self.cur = move_to;
-                    Ok(())
-                }
+    fn tag(&mut self, tag: &str) -> Result<(), &'static str> {
+        match self.get_cur_item_mut() {
+            Some(item) if !item.has_tag(tag) => {
+                item.text = format!("{} +{tag}", item.text);
+                Ok(())
             }
-        } else {
-            Err("Can't drag down. List is empty.")
+            Some(_) => Ok(()),
+            None => Err("Can't tag item. List is empty."),
         }
     }
 
-    fn first(&mut self) {
-        self.cur = 0;
+    /// Folds or unfolds the current item, or its parent if it has no subtasks.
+    /// Toggles the fold when `folded` is `None`.
+    fn fold(&mut self, folded: Option<bool>) -> Result<(), &'static str> {
+        let item = self
+            .get_cur_item()
+            .ok_or("Nothing to fold. List is empty.")?;
+        let target = if item.has_children() {
+            self.cur
+        } else {
+            item.parent
+                .ok_or("Nothing to fold. Item has no subtasks.")?
+        };
+
+        let item = &mut self.list[target];
+        item.folded = folded.unwrap_or(!item.folded);
+        Ok(())
     }
 
-    fn half(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = self.list.len() / 2;
-            } else {
-                let num_roots = self.list.iter().filter(|item| item.is_root()).count();
-                self.cur = self
-                    .list
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, item)| item.is_root())
-                    .nth(num_roots / 2)
-                    .map(|(i, _)| i)
-                    .unwrap_or(self.cur);
-            }
+    fn fold_all(&mut self, folded: bool) {
+        for item in self.list.iter_mut() {
+            item.folded = folded && item.has_children();
         }
     }
 
-    fn last(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = self.list.len() - 1;
-            } else {
-                self.cur = self
-                    .list
-                    .iter()
-                    .rposition(|item| item.is_root())
-                    .unwrap_or(0);
+    fn toggle_selected(&mut self) -> Result<(), &'static str> {
+        match self.get_cur_item_mut() {
+            Some(item) => {
+                item.selected = !item.selected;
+                Ok(())
             }
+            None => Err("Can't select item. List is empty."),
         }
     }
 
-    fn children_cnt(&self, parent: usize) -> usize {
-        let mut cnt = 0;
-        if let Some(item) = self.list.get(parent) {
-            if item.children.is_empty() {
-                return 0;
+    fn clear_selection(&mut self) {
+        self.end_visual();
+        self.list.iter_mut().for_each(|item| item.selected = false);
+    }
+
+    /// Runs `op` with the cursor on every selected item in turn, the last ones
+    /// first if `rev`, or just on the current item when nothing is selected.
+    /// Subtasks of selected items are left to their parents unless `each`.
+    fn for_selected(
+        &mut self,
+        rev: bool,
+        each: bool,
+        mut op: impl FnMut(&mut List) -> Result<(), &'static str>,
+    ) -> Result<(), &'static str> {
+        self.end_visual();
+        if !self.list.iter().any(|item| item.selected) {
+            return op(self);
+        }
+
+        if !each {
+            // Parents come before their subtasks
+            let mut covered = vec![false; self.list.len()];
+            for i in 0..self.list.len() {
+                if let Some(p) = self.list[i].parent {
+                    covered[i] = covered[p] || self.list[p].selected;
+                }
             }
+            for (item, covered) in self.list.iter_mut().zip(covered) {
+                item.selected &= !covered;
+            }
+        }
 
-            for child in item.children.iter() {
-                cnt += 1 + self.children_cnt(*child);
+        loop {
+            let next = if rev {
+                self.list.iter().rposition(|item| item.selected)
+            } else {
+                self.list.iter().position(|item| item.selected)
+            };
+            match next {
+                Some(i) => {
+                    self.list[i].selected = false;
+                    self.cur = i;
+                    op(self)?;
+                }
+                None => return Ok(()),
             }
         }
-        cnt
     }
 
     fn shift_indices(&mut self, by: isize, from: usize, to: Option<usize>, parent: Option<usize>) {
@@ -400,7 +1458,8 @@ impl List {
             }
         }
 
-        let item = Item::new(String::new(), Local::now(), None, 1);
+        let mut item = Item::new_todo(None);
+        item.state = self.column_state(self.column);
 
         self.shift_indices(1, self.cur, None, None);
         self.list.insert(self.cur, item);
@@ -410,7 +1469,7 @@ impl List {
 
     fn append(&mut self) -> Result<(), &'static str> {
         if self.get_cur_item().is_some() {
-            let item = Item::new(String::new(), Local::now(), Some(self.cur), 1);
+            let item = Item::new_todo(Some(self.cur));
 
             self.unmark_parents(Some(self.cur));
             self.shift_indices(1, 0, None, Some(self.cur));
@@ -442,6 +1501,7 @@ impl List {
         if let Some(item) = self.get_cur_item() {
             let child_cnt = self.children_cnt(self.cur) + 1;
             let parent = item.parent;
+            let active = item.is_active();
 
             if let Some(parent) = parent {
                 self.list[parent].children.retain(|&x| x != self.cur);
@@ -452,7 +1512,7 @@ impl List {
                     }
                 }
 
-                if self.list[parent].act_cnt > 1 {
+                if active && self.list[parent].act_cnt > 1 {
                     self.list[parent].act_cnt -= 1;
                 }
             }
@@ -482,6 +1542,7 @@ impl List {
             if item.act_cnt == 1 {
                 item.act_cnt = 0;
                 item.date = Local::now();
+                item.clock_out();
 
                 if let Some(p) = parent {
                     self.list[p].act_cnt -= 1;
@@ -532,49 +1593,33 @@ impl List {
 
     fn edit(&mut self, cur: &mut usize, key: i32) {
         if let Some(item) = self.get_cur_item_mut() {
-            *cur = min(*cur, item.text.len());
-
-            match key {
-                32..=126 => {
-                    if *cur > item.text.len() {
-                        item.text.push(key as u8 as char);
-                    } else {
-                        item.text.insert(*cur, key as u8 as char);
-                    }
-                    *cur += 1;
-                }
-                constants::KEY_LEFT => {
-                    if *cur > 0 {
-                        *cur -= 1;
-                    }
-                }
-                constants::KEY_RIGHT => {
-                    if *cur < item.text.len() {
-                        *cur += 1;
-                    }
-                }
-                constants::KEY_BACKSPACE | 127 => {
-                    // 127 is backspace
-                    if *cur > 0 {
-                        *cur -= 1;
-                        if *cur < item.text.len() {
-                            item.text.remove(*cur);
-                        }
-                    }
-                }
-                constants::KEY_DC => {
-                    if *cur < item.text.len() {
-                        item.text.remove(*cur);
-                    }
-                }
-                constants::KEY_HOME | 1 => *cur = 0, // 1 is ctrl + a
-                constants::KEY_END | 5 => *cur = item.text.len(), // 5 is ctrl + e
-                _ => {}
-            }
+            edit_text(&mut item.text, cur, key);
         }
     }
 }
 
+/// Makes the next occurrence of a recurring item along with its subtasks.
+fn renew(mut entries: Entries) -> Entries {
+    let today = Local::now().date_naive();
+
+    for (item, _) in entries.iter_mut() {
+        let new = Item::new_todo(item.parent);
+        item.uid = None;
+        item.state = None;
+        item.clock.clear();
+        item.act_cnt = new.act_cnt;
+        item.date = new.date;
+        item.created = new.created;
+        item.selected = false;
+    }
+    let root = &mut entries[0].0;
+    if let Some(rule) = &root.recur {
+        root.due = rule.next_due(root.due, today).or(root.due);
+    }
+
+    entries
+}
+
 #[derive(Debug)]
 pub struct TodoApp {
     message: String,
@@ -583,6 +1628,8 @@ pub struct TodoApp {
     operation_stack: Vec<Operation>,
     todos: List,
     dones: List,
+    read_as: Option<Format>,
+    preamble: Vec<String>,
 }
 
 impl TodoApp {
@@ -594,6 +1641,8 @@ impl TodoApp {
             operation_stack: Vec::new(),
             todos: List::new(),
             dones: List::new(),
+            read_as: None,
+            preamble: Vec::new(),
         }
     }
 
@@ -610,23 +1659,51 @@ impl TodoApp {
     }
 
     pub fn is_cur_todo(&self, todo: &Item) -> bool {
-        self.todos.get_cur_item().map_or(false, |t| t == todo)
+        // Yanked and pasted copies are equal, only the item itself is current
+        self.todos
+            .list
+            .get(self.todos.cur)
+            .is_some_and(|item| ptr::eq(item, todo))
     }
 
     pub fn is_cur_done(&self, done: &Item) -> bool {
-        self.dones.get_cur_item().map_or(false, |d| d == done)
+        self.dones
+            .list
+            .get(self.dones.cur)
+            .is_some_and(|item| ptr::eq(item, done))
     }
 
     pub fn get_message(&self) -> &String {
         &self.message
     }
 
-    pub fn iter_todos(&self) -> ListIter {
-        ListIter {
-            obj: &self.todos,
-            cur: 0,
-            skip_children: self.hide_subs,
-        }
+    pub fn iter_todos(&self) -> ListIter<'_> {
+        self.todos.iter(self.hide_subs)
+    }
+
+    /// The items of both panels matching the filter, regardless of the folds
+    /// and the board.
+    pub fn iter_matching(&self) -> impl Iterator<Item = (&Item, usize)> {
+        self.todos.iter_matching().chain(self.dones.iter_matching())
+    }
+
+    /// The TODO items in the `column` of the board.
+    pub fn iter_column(&self, column: usize) -> ListIter<'_> {
+        self.todos.iter_column(column, self.hide_subs)
+    }
+
+    /// Splits the TODO panel into the given columns, the last one of `names`
+    /// stands for the DONE panel. The column of an item is kept in a `state:` token.
+    pub fn set_columns(&mut self, names: &[String]) {
+        let names = &names[..names.len().saturating_sub(1)];
+        self.todos.columns = names.iter().map(|name| state(name)).collect();
+        self.todos.column = 0;
+    }
+
+    /// The column of the board the cursor is in, `None` in the DONE panel or
+    /// without a board.
+    pub fn get_column(&self) -> Option<usize> {
+        (self.panel == Panel::Todo && !self.todos.columns.is_empty()).then_some(self.todos.column)
     }
 
     pub fn get_todos_n(&self, full: bool) -> usize {
@@ -641,12 +1718,18 @@ impl TodoApp {
         }
     }
 
-    pub fn iter_dones(&self) -> ListIter {
-        ListIter {
-            obj: &self.dones,
-            cur: 0,
-            skip_children: self.hide_subs,
-        }
+    pub fn iter_dones(&self) -> ListIter<'_> {
+        self.dones.iter(self.hide_subs)
+    }
+
+    /// Every TODO item along with the subtasks, regardless of the view.
+    pub fn all_todos(&self) -> impl Iterator<Item = &Item> {
+        self.todos.list.iter()
+    }
+
+    /// Every done item along with the done subtasks, regardless of the view.
+    pub fn all_dones(&self) -> impl Iterator<Item = &Item> {
+        self.dones.list.iter()
     }
 
     pub fn get_dones_n(&self, full: bool) -> usize {
@@ -665,9 +1748,91 @@ impl TodoApp {
         self.message.clear();
     }
 
-    pub fn parse(&mut self, file_path: &str) {
-        let file = File::open(file_path);
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    /// Loads the list at `file_path`, a missing file is a new list. The error
+    /// tells the file and the line that could not be read.
+    pub fn parse(&mut self, file_path: &str, format: Format) -> Result<(), String> {
+        match File::open(file_path) {
+            Ok(file) => {
+                self.read(io::BufReader::new(file), file_path, format)?;
+                self.message = format!("Loaded '{file_path}' file.")
+            }
+            Err(err) => {
+                if err.kind() == io::ErrorKind::NotFound {
+                    self.message = format!("File '{file_path}' not found. Creating a new one.");
+                } else {
+                    self.message =
+                        format!("Error occured while opening the file '{file_path}': {err:?}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read(
+        &mut self,
+        reader: impl BufRead,
+        file_path: &str,
+        format: Format,
+    ) -> Result<(), String> {
+        match format {
+            Format::List => self.parse_list(reader, file_path)?,
+            Format::Markdown => self.load_with(markdown::parse(reader), file_path)?,
+            Format::TodoTxt => {
+                let parsed = todotxt::parse(reader).map(|entries| (Vec::new(), entries));
+                self.load_with(parsed, file_path)?
+            }
+            Format::Org => self.load_with(org::parse(reader), file_path)?,
+        }
+        self.read_as = Some(format);
+        Ok(())
+    }
+
+    /// Loads the items read by a format along with the lines before the first one.
+    fn load_with(
+        &mut self,
+        parsed: io::Result<(Vec<String>, Entries)>,
+        file_path: &str,
+    ) -> Result<(), String> {
+        let (preamble, entries) = parsed.map_err(|err| format!("{file_path}: {err}"))?;
+        self.preamble = preamble;
+        self.load_entries(entries);
+        Ok(())
+    }
+
+    fn load_entries(&mut self, entries: Entries) {
+        let all = List::from_levels(entries);
+        let mut todos = Vec::new();
+        let mut dones = Vec::new();
+        let mut is_todo = true;
+
+        // Whole subtrees go to the panel matching the state of their root, which
+        // keeps its position to be written back in place
+        let mut pos = 0;
+        for (item, level) in all.iter_all() {
+            let mut item = item.clone();
+            if item.is_root() {
+                is_todo = item.is_active();
+                item.pos = Some(pos);
+                pos += 1;
+            }
+            if is_todo {
+                todos.push((item, level));
+            } else {
+                dones.push((item, level));
+            }
+        }
+
+        let columns = std::mem::take(&mut self.todos.columns);
+        self.todos = List::from_levels(todos);
+        self.todos.columns = columns;
+        self.dones = List::from_levels(dones);
+    }
 
+    fn parse_list(&mut self, reader: impl BufRead, file_path: &str) -> Result<(), String> {
         let sep = SEP;
         let re_indent = Regex::new(r"^((\s{4})*)\S+").unwrap();
         let mut panel = Panel::Todo;
@@ -676,86 +1841,66 @@ impl TodoApp {
         let mut cnt_todos = 0;
         let mut cur_indent = 0;
 
-        match file {
-            Ok(file) => {
-                for (i, line) in io::BufReader::new(file).lines().enumerate() {
-                    let line = line.unwrap();
-                    let parent: Option<usize>;
-
-                    if line == sep {
-                        if panel == Panel::Todo {
-                            cur_indent = 0;
-                            stack.clear();
-                            panel = Panel::Done;
-                        } else {
-                            eprintln!("[ERROR]: {}:{}: invalid separator", file_path, i + 1);
-                            exit(1);
-                        }
-                        continue;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|err| format!("{file_path}: {err}"))?;
+            let parent: Option<usize>;
+
+            if line == sep {
+                if panel == Panel::Todo {
+                    cur_indent = 0;
+                    stack.clear();
+                    panel = Panel::Done;
+                } else {
+                    return Err(format!("{}:{}: invalid separator", file_path, i + 1));
+                }
+                continue;
+            }
+
+            if let Some(m) = re_indent.captures(&line) {
+                let indent = m[1].len() / INDENT_SIZE;
+                match indent.cmp(&cur_indent) {
+                    Ordering::Less => {
+                        (0..(cur_indent - indent + 1)).map(|_| stack.pop()).last();
+                        cur_indent = indent;
                     }
+                    Ordering::Equal => drop(stack.pop()),
+                    Ordering::Greater => cur_indent = indent,
+                }
+                parent = stack.last().copied();
+                stack.push(i);
+            } else {
+                return Err(format!("{}:{}: invalid indentation", file_path, i + 1));
+            }
 
-                    if let Some(m) = re_indent.captures(&line) {
-                        let indent = m[1].len() / INDENT_SIZE;
-                        match indent.cmp(&cur_indent) {
-                            Ordering::Less => {
-                                (0..(cur_indent - indent + 1)).map(|_| stack.pop()).last();
-                                cur_indent = indent;
-                            }
-                            Ordering::Equal => drop(stack.pop()),
-                            Ordering::Greater => cur_indent = indent,
+            match panel {
+                Panel::Todo => match self.parse_todo(&line, parent) {
+                    Err(e) => return Err(format!("{}:{}: {}", file_path, i + 1, e)),
+                    Ok(todo) => {
+                        let active = todo.is_active();
+                        cnt_todos += 1;
+
+                        self.todos.add_item(todo);
+                        if let Some(parent) = parent {
+                            self.todos.add_child_to(parent, i, active);
                         }
-                        parent = stack.last().copied();
-                        stack.push(i);
-                    } else {
-                        eprintln!("[ERROR]: {}:{}: invalid indentation", file_path, i + 1);
-                        exit(1);
                     }
-
-                    match panel {
-                        Panel::Todo => match self.parse_todo(&line, parent) {
-                            Err(e) => {
-                                eprintln!("[ERROR]: {}:{}: {}", file_path, i + 1, e);
-                                exit(1);
-                            }
-                            Ok(todo) => {
-                                let active = todo.is_active();
-                                cnt_todos += 1;
-
-                                self.todos.add_item(todo);
-                                if let Some(parent) = parent {
-                                    self.todos.add_child_to(parent, i, active);
-                                }
-                            }
-                        },
-                        Panel::Done => {
-                            let parent = parent.map(|p| p - cnt_todos - 1);
-                            match self.parse_done(&line, parent) {
-                                Err(e) => {
-                                    eprintln!("[ERROR]: {}:{}: {}", file_path, i + 1, e);
-                                    exit(1);
-                                }
-                                Ok(done) => {
-                                    let i = i - cnt_todos - 1;
-                                    self.dones.add_item(done);
-                                    if let Some(parent) = parent {
-                                        self.dones.add_child_to(parent, i, false);
-                                    }
-                                }
+                },
+                Panel::Done => {
+                    let parent = parent.map(|p| p - cnt_todos - 1);
+                    match self.parse_done(&line, parent) {
+                        Err(e) => return Err(format!("{}:{}: {}", file_path, i + 1, e)),
+                        Ok(done) => {
+                            let i = i - cnt_todos - 1;
+                            self.dones.add_item(done);
+                            if let Some(parent) = parent {
+                                self.dones.add_child_to(parent, i, false);
                             }
                         }
                     }
                 }
-                self.message = format!("Loaded '{file_path}' file.")
-            }
-            Err(err) => {
-                if err.kind() == io::ErrorKind::NotFound {
-                    self.message = format!("File '{file_path}' not found. Creating a new one.");
-                } else {
-                    self.message =
-                        format!("Error occured while opening the file '{file_path}': {err:?}");
-                }
             }
         }
+        Ok(())
     }
 
     fn parse_todo(&mut self, line: &str, parent: Option<usize>) -> Result<Item, &'static str> {
@@ -763,58 +1908,159 @@ impl TodoApp {
 
         if let Some(caps) = re_todo.captures(line) {
             let act_cnt = if caps[2].is_empty() { 0 } else { 1 };
-            Ok(Item::new(
-                caps[3].trim().to_string(),
-                Local::now(),
-                parent,
-                act_cnt,
-            ))
+            let mut item = Item::new(caps[3].trim().to_string(), Local::now(), parent, act_cnt);
+            item.parse_meta();
+            Ok(item)
         } else {
             Err("invalid format for a TODO item")
         }
     }
 
     fn parse_done(&mut self, line: &str, parent: Option<usize>) -> Result<Item, &str> {
-        let re_done = Regex::new(r"^(\s{4})*DONE\((.*)\): (.*)$").unwrap();
+        let mut item = Item::from_done_line(line)?;
+        item.parent = parent;
+        Ok(item)
+    }
 
-        if let Some(caps) = re_done.captures(line) {
-            let date = DateTime::parse_from_str(&caps[2], DATE_FMT);
-            if let Ok(d) = date {
-                Ok(Item::new(caps[3].trim().to_string(), d.into(), parent, 0))
-            } else {
-                Err("invalid date format for a DONE item")
-            }
-        } else {
-            Err("invalid format for a DONE item")
+    pub fn save(&self, file_path: &str, format: Format, progress: Progress) -> io::Result<()> {
+        let mut file = File::create(file_path)?;
+        self.write(&mut file, format, progress)
+    }
+
+    fn write(&self, file: &mut impl Write, format: Format, progress: Progress) -> io::Result<()> {
+        let (todos, dones) = (self.todos.iter_all(), self.dones.iter_all());
+        // The lines that are not items only make sense in the format they were read in
+        let kept = (self.read_as == Some(format)).then_some(self.preamble.as_slice());
+        match format {
+            Format::List => self.save_list(file),
+            Format::Markdown => markdown::write(file, kept, todos, dones, progress),
+            Format::TodoTxt => todotxt::write(file, todos, dones),
+            Format::Org => org::write(file, kept, todos, dones, progress),
         }
     }
 
-    pub fn save(&mut self, file_path: &str) -> std::io::Result<()> {
+    fn save_list(&self, file: &mut impl Write) -> io::Result<()> {
         let sep = SEP;
-        self.hide_subs = false;
 
-        let mut file = File::create(file_path)?;
-        for (todo, level) in self.iter_todos() {
+        for (todo, level) in self.todos.iter_all() {
             let indent = " ".repeat(level * INDENT_SIZE);
             let act = if todo.is_active() { "*" } else { "" };
-            writeln!(file, "{indent}TODO({act}): {}", todo.text).unwrap();
+            writeln!(file, "{indent}TODO({act}): {}", todo.text_with_meta())?;
         }
 
-        writeln!(file, "{sep}").unwrap();
+        writeln!(file, "{sep}")?;
 
-        for (done, level) in self.iter_dones() {
-            let indent = " ".repeat(level * INDENT_SIZE);
-            let date = done.date.format(DATE_FMT);
-            writeln!(file, "{indent}DONE({date}): {}", done.text).unwrap();
+        for (done, level) in self.dones.iter_all() {
+            writeln!(file, "{}", done.done_line(level))?;
         }
 
         Ok(())
     }
 
+    /// Hands the DONE items done before `before`, or all of them, over to `write`
+    /// and drops them from the list unless it fails. Returns how many root items
+    /// were archived. They can't come back by undo, so the history is cleared.
+    pub fn archive(
+        &mut self,
+        before: Option<NaiveDate>,
+        write: impl FnOnce(Entries) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        assert!(!self.is_in_edit(), "Can't archive items while in edit mode");
+
+        let mut kept = Vec::new();
+        let mut archived = Vec::new();
+        let mut old = false;
+        for (item, level) in self.dones.entries() {
+            if level == 0 {
+                old = before.is_none_or(|before| item.get_done_date() < before);
+            }
+            if old {
+                archived.push((item, level));
+            } else {
+                kept.push((item, level));
+            }
+        }
+
+        let n = archived.iter().filter(|(_, level)| *level == 0).count();
+        if n > 0 {
+            write(archived)?;
+            self.dones.end_visual();
+            self.dones.rebuild(kept);
+            self.operation_stack.clear();
+            self.todos.state_stack.clear();
+            self.dones.state_stack.clear();
+        }
+        Ok(n)
+    }
+
+    /// Brings the TODO panel in sync with the comments found in `dir`: new comments
+    /// are added as root TODOs, the known ones get their location updated and the
+    /// ones that are gone from the code are marked as done.
+    pub fn merge_scanned(&mut self, dir: &str, comments: Vec<Comment>) -> ScanStats {
+        let scope = normalize(Path::new(dir));
+        let mut stats = ScanStats {
+            added: 0,
+            updated: 0,
+            resolved: 0,
+        };
+        let mut seen_todos = vec![false; self.todos.list.len()];
+        let mut seen_dones = vec![false; self.dones.list.len()];
+
+        for comment in comments {
+            // Words like `due:` in the comment are read as they are on loading the list
+            let mut new = Item::new_todo(None);
+            new.text = comment.text.clone();
+            new.parse_meta();
+
+            let item = if let Some(i) = self.todos.find_source(&comment, &new.text, &seen_todos) {
+                seen_todos[i] = true;
+                &mut self.todos.list[i]
+            } else if let Some(i) = self.dones.find_source(&comment, &new.text, &seen_dones) {
+                seen_dones[i] = true;
+                &mut self.dones.list[i]
+            } else {
+                new.source = Some((comment.path, comment.line));
+                self.todos.add_item(new);
+                seen_todos.push(true);
+                stats.added += 1;
+                continue;
+            };
+
+            if let Some((_, line)) = item.source.as_mut() {
+                if *line != comment.line {
+                    *line = comment.line;
+                    stats.updated += 1;
+                }
+            }
+        }
+
+        for (i, item) in self.todos.list.iter_mut().enumerate() {
+            let in_scope = item
+                .source
+                .as_ref()
+                .is_some_and(|(path, _)| scope == "." || Path::new(path).starts_with(&scope));
+            if !seen_todos[i] && in_scope && item.act_cnt == 1 {
+                item.act_cnt = 0;
+                item.date = Local::now();
+                stats.resolved += 1;
+            }
+        }
+        self.todos.fix_counts();
+
+        stats
+    }
+
     pub fn toggle_panel(&mut self) {
         assert!(!self.is_in_edit(), "Can't toggle panel while in edit mode.");
 
-        self.panel = self.panel.togle();
+        // The columns of a board come one after another before the DONE panel
+        let columns = self.todos.columns.len();
+        if self.panel == Panel::Todo && self.todos.column + 1 < columns {
+            self.todos.column += 1;
+        } else {
+            self.todos.column = 0;
+            self.panel = self.panel.togle();
+        }
     }
 
     pub fn toggle_subtasks(&mut self) {
@@ -824,18 +2070,141 @@ impl TodoApp {
         );
 
         self.hide_subs = !self.hide_subs;
+        self.fix_cursor();
+    }
+
+    /// Keeps the cursors on the shown items after the view or the lists change.
+    pub fn fix_cursor(&mut self) {
+        self.todos.fix_cursor(!self.hide_subs);
+        self.dones.fix_cursor(!self.hide_subs);
+        self.todos.update_visual(!self.hide_subs);
+        self.dones.update_visual(!self.hide_subs);
+    }
 
-        if self.hide_subs {
-            if let Some(cur_todo) = self.todos.get_cur_item() {
-                if !cur_todo.is_root() {
-                    self.todos.up(!self.hide_subs);
+    pub fn get_filter(&self) -> Option<&Query> {
+        self.todos.filter.as_ref()
+    }
+
+    /// Shows only the items matching `query` in both panels, `None` shows everything.
+    pub fn set_filter(&mut self, query: Option<Query>) {
+        self.todos.filter = query.clone();
+        self.dones.filter = query.clone();
+        self.fix_cursor();
+
+        match query {
+            Some(query) => {
+                let today = Local::now().date_naive();
+                let found = [&self.todos, &self.dones]
+                    .iter()
+                    .any(|list| list.list.iter().any(|item| query.matches(item, today)));
+                if found {
+                    self.message = format!("Showing the items matching '{query}'.");
+                } else {
+                    self.message = format!("No items match '{query}'.");
                 }
             }
-            if let Some(cur_done) = self.dones.get_cur_item() {
-                if !cur_done.is_root() {
-                    self.dones.up(!self.hide_subs);
-                }
+            None => {
+                self.todos.day = None;
+                self.dones.day = None;
+                self.message.push_str("Filter cleared.");
+            }
+        }
+    }
+
+    pub fn get_day_filter(&self) -> Option<NaiveDate> {
+        self.todos.day
+    }
+
+    /// Shows only the items due or done on `day` in both panels, on top of the tag filter.
+    pub fn set_day_filter(&mut self, day: NaiveDate) {
+        self.todos.day = Some(day);
+        self.dones.day = Some(day);
+        self.fix_cursor();
+
+        let today = Local::now().date_naive();
+        let found = [&self.todos, &self.dones]
+            .iter()
+            .any(|list| list.list.iter().any(|item| list.matches(item, today)));
+        let day = day.format("%Y-%m-%d");
+        if found {
+            self.message = format!("Showing the items due or done on {day}.");
+        } else {
+            self.message = format!("No items due or done on {day}.");
+        }
+    }
+
+    /// Sorts the items of the panel as a single operation, see `List::sort()`.
+    pub fn sort_items(&mut self, key: SortKey, deep: bool) {
+        assert!(!self.is_in_edit(), "Can't sort while in edit mode.");
+
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+
+        list.record_state();
+        match list.sort(key, deep) {
+            Ok(()) => {
+                self.operation_stack
+                    .push(Operation::new(Action::Sort, self.panel));
+                self.message = format!("Sorted by {key}.");
             }
+            Err(err) => {
+                self.message.push_str(err);
+                list.revert_state().unwrap();
+            }
+        }
+    }
+
+    pub fn go_to(&mut self, n: usize) {
+        assert!(
+            !self.is_in_edit(),
+            "Can't go to an item while in edit mode."
+        );
+
+        let res = match self.panel {
+            Panel::Todo => self.todos.go_to(n, !self.hide_subs),
+            Panel::Done => self.dones.go_to(n, !self.hide_subs),
+        };
+        if let Err(err) = res {
+            self.message.push_str(err);
+        }
+    }
+
+    /// Moves the cursor to the `i`-th item of a panel, unfolding its parents
+    /// and dropping the filters that hide it.
+    pub fn reveal(&mut self, in_dones: bool, i: usize) {
+        assert!(
+            !self.is_in_edit(),
+            "Can't jump to an item while in edit mode."
+        );
+
+        self.panel = if in_dones { Panel::Done } else { Panel::Todo };
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        if i >= list.list.len() {
+            return;
+        }
+
+        list.end_visual();
+        list.cur = i;
+        list.column = list.item_columns()[i];
+        let mut parent = list.list[i].parent;
+        while let Some(p) = parent {
+            list.list[p].folded = false;
+            parent = list.list[p].parent;
+        }
+
+        if !list.visible(!self.hide_subs)[i] {
+            self.hide_subs &= list.list[i].is_root();
+            for list in [&mut self.todos, &mut self.dones] {
+                list.filter = None;
+                list.day = None;
+                list.unblocked = false;
+            }
+            self.message.push_str("Showing all the items.");
         }
     }
 
@@ -859,8 +2228,8 @@ impl TodoApp {
     pub fn go_top(&mut self) {
         assert!(!self.is_in_edit(), "Can't go top while in edit mode.");
         match self.panel {
-            Panel::Todo => self.todos.first(),
-            Panel::Done => self.dones.first(),
+            Panel::Todo => self.todos.first(!self.hide_subs),
+            Panel::Done => self.dones.first(!self.hide_subs),
         }
     }
 
@@ -880,83 +2249,92 @@ impl TodoApp {
         }
     }
 
-    pub fn drag_up(&mut self) {
+    /// Applies `op` to the selected items of the panel, or to the current item when
+    /// nothing is selected, as a single operation reverted as a whole on any error.
+    fn apply(
+        &mut self,
+        action: Action,
+        rev: bool,
+        each: bool,
+        mut op: impl FnMut(&mut List, &mut List) -> Result<(), &'static str>,
+    ) -> bool {
+        let (list, other) = match self.panel {
+            Panel::Todo => (&mut self.todos, &mut self.dones),
+            Panel::Done => (&mut self.dones, &mut self.todos),
+        };
+        let both = action == Action::Transfer;
+
+        list.record_state();
+        if both {
+            other.record_state();
+        }
+        match list.for_selected(rev, each, |list| op(list, other)) {
+            Ok(()) => {
+                self.operation_stack
+                    .push(Operation::new(action, self.panel));
+                true
+            }
+            Err(err) => {
+                self.message.push_str(err);
+                list.revert_state().unwrap();
+                if both {
+                    other.revert_state().unwrap();
+                }
+                false
+            }
+        }
+    }
+
+    pub fn drag_up(&mut self, count: usize) {
         assert!(!self.is_in_edit(), "Can't drag up while in edit mode.");
-        match self.panel {
-            Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.drag_up() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragUp, Panel::Todo)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.todos.revert_state().unwrap();
-                    }
-                };
-            }
-            Panel::Done => {
-                self.dones.record_state();
-                match self.dones.drag_up() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragUp, Panel::Done)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.dones.revert_state().unwrap();
-                    }
-                };
-            }
-        }
+
+        self.apply(Action::DragUp, false, false, |list, _| {
+            list.repeat(count, List::drag_up)
+        });
     }
 
-    pub fn drag_down(&mut self) {
+    pub fn drag_down(&mut self, count: usize) {
         assert!(!self.is_in_edit(), "Can't drag down while in edit mode.");
 
-        match self.panel {
-            Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.drag_down() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragDown, Panel::Todo)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.todos.revert_state().unwrap();
-                    }
-                };
-            }
-            Panel::Done => {
-                self.dones.record_state();
-                match self.dones.drag_down() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragDown, Panel::Done)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.dones.revert_state().unwrap();
-                    }
-                };
-            }
-        }
+        self.apply(Action::DragDown, true, false, |list, _| {
+            list.repeat(count, List::drag_down)
+        });
+    }
+
+    pub fn indent_item(&mut self, count: usize) {
+        assert!(!self.is_in_edit(), "Can't indent while in edit mode.");
+
+        self.apply(Action::Indent, false, false, |list, _| {
+            list.repeat(count, List::indent)
+        });
+    }
+
+    pub fn outdent_item(&mut self, count: usize) {
+        assert!(!self.is_in_edit(), "Can't outdent while in edit mode.");
+
+        // The last items go first to keep their order under the grandparent
+        self.apply(Action::Outdent, true, false, |list, _| {
+            list.repeat(count, List::outdent)?;
+            list.settle();
+            Ok(())
+        });
     }
 
     pub fn mark_item(&mut self) {
         assert!(!self.is_in_edit(), "Can't mark item while in edit mode");
 
         match self.panel {
+            // Subtasks go first so that their parents can be marked after them
             Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.mark() {
-                    Ok(()) => {
-                        self.operation_stack
-                            .push(Operation::new(Action::Mark, Panel::Todo));
-                    }
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.todos.revert_state().unwrap();
+                self.apply(Action::Mark, true, true, |list, _| {
+                    if list
+                        .get_cur_item()
+                        .is_some_and(|item| item.is_active() && list.is_blocked(item))
+                    {
+                        return Err("Can't mark item. Item is blocked.");
                     }
-                };
+                    list.mark()
+                });
             }
             Panel::Done => self
                 .message
@@ -967,32 +2345,75 @@ impl TodoApp {
     pub fn transfer_item(&mut self) {
         assert!(!self.is_in_edit(), "Can't transfer item while in edit mode");
 
-        self.todos.record_state();
-        self.dones.record_state();
+        let to_dones = self.panel == Panel::Todo;
+        let mut shifted = false;
+        if self.apply(Action::Transfer, false, false, |list, other| {
+            // On a board the active items go through the columns first
+            if to_dones
+                && !list.columns.is_empty()
+                && list.get_cur_item().is_some_and(|item| item.is_active())
+            {
+                shifted = true;
+                return list.shift_column(1);
+            }
+            if to_dones
+                && list
+                    .get_cur_item()
+                    .is_some_and(|item| list.is_blocked(item))
+            {
+                return Err("Can't transfer item. Item is blocked.");
+            }
+
+            let cur = list.cur;
+            let renewed = list
+                .get_cur_item()
+                .filter(|item| to_dones && item.recur.is_some())
+                .map(|_| renew(list.subtree(cur)));
 
-        let result = match self.panel {
-            Panel::Todo => self.todos.transfer(&mut self.dones),
-            Panel::Done => self.dones.transfer(&mut self.todos),
-        };
-        match result {
-            Ok(()) => match self.panel {
-                Panel::Todo => {
-                    self.operation_stack
-                        .push(Operation::new(Action::Transfer, Panel::Todo));
-                    self.message.push_str("Done! Great job!");
-                }
-                Panel::Done => {
-                    self.operation_stack
-                        .push(Operation::new(Action::Transfer, Panel::Done));
-                    self.message.push_str("Not done yet? Keep going!")
+            // Only the next occurrence keeps the rule, so it is renewed once
+            if let Some(item) = list.get_cur_item_mut().filter(|_| renewed.is_some()) {
+                item.recur = None;
+            }
+            let to = other.list.len();
+            list.transfer(other)?;
+            // Items coming back from DONE go to the last column of the board
+            other.list[to].state = other.column_state(other.columns.len().saturating_sub(1));
+
+            // The next occurrence takes the place of the done one
+            if let Some(renewed) = renewed {
+                let mut entries = list.entries();
+                entries.splice(cur..cur, renewed);
+                list.rebuild(entries);
+                list.cur = cur;
+            }
+            Ok(())
+        }) {
+            match self.panel {
+                Panel::Todo if shifted => self.message.push_str("Moved to the next column."),
+                Panel::Todo => self.message.push_str("Done! Great job!"),
+                Panel::Done => self.message.push_str("Not done yet? Keep going!"),
+            }
+        }
+    }
+
+    /// Moves the current item to the previous column of the board, the items
+    /// of the DONE panel go back to the TODO panel.
+    pub fn transfer_back(&mut self) {
+        assert!(!self.is_in_edit(), "Can't transfer item while in edit mode");
+
+        match self.panel {
+            Panel::Todo if self.todos.columns.is_empty() => self
+                .message
+                .push_str("Can't move item back. Item is not done yet."),
+            Panel::Todo => {
+                if self.apply(Action::Transfer, false, false, |list, _| {
+                    list.shift_column(-1)
+                }) {
+                    self.message.push_str("Moved to the previous column.");
                 }
-            },
-            Err(err) => {
-                self.message.push_str(err);
-                self.todos.revert_state().unwrap();
-                self.dones.revert_state().unwrap();
             }
-        };
+            Panel::Done => self.transfer_item(),
+        }
     }
 
     pub fn delete_item(&mut self) {
@@ -1000,49 +2421,98 @@ impl TodoApp {
 
         match self.panel {
             Panel::Todo => {
-                if self.todos.is_at_sub() {
-                    self.todos.record_state();
-                    match self.todos.delete() {
-                        Ok(()) => {
-                            self.message.push_str("A TODO subtask deleted.");
-                            self.operation_stack
-                                .push(Operation::new(Action::Delete, Panel::Todo));
-                        }
-                        Err(err) => {
-                            self.message.push_str(err);
-                            self.todos.revert_state().unwrap();
-                        }
-                    };
-                } else {
-                    self.message
-                        .push_str("Can't delete a TODO item. Transfer it to DONEs first.");
+                let deleted = self.apply(Action::Delete, false, false, |list, _| {
+                    if list.is_at_sub() {
+                        list.delete()
+                    } else {
+                        Err("Can't delete a TODO item. Transfer it to DONEs first.")
+                    }
+                });
+                if deleted {
+                    self.message.push_str("A TODO subtask deleted.");
                 }
             }
             Panel::Done => {
-                if self.dones.is_at_root() {
-                    self.dones.record_state();
-                    match self.dones.delete() {
-                        Ok(()) => {
-                            self.message.push_str("A DONE item deleted.");
-                            self.operation_stack
-                                .push(Operation::new(Action::Delete, Panel::Done));
-                        }
-                        Err(err) => {
-                            self.message.push_str(err);
-                            self.dones.revert_state().unwrap();
-                        }
-                    };
-                } else {
-                    self.message
-                        .push_str("Can't delete a subtask. Only root items can be deleted.");
+                let deleted = self.apply(Action::Delete, false, false, |list, _| {
+                    if list.is_at_root() {
+                        list.delete()
+                    } else {
+                        Err("Can't delete a subtask. Only root items can be deleted.")
+                    }
+                });
+                if deleted {
+                    self.message.push_str("A DONE item deleted.");
                 }
             }
         }
     }
 
+    /// Adds `+tag` to the text of the selected items or of the current one.
+    pub fn tag_items(&mut self, tag: &str) {
+        assert!(!self.is_in_edit(), "Can't tag items while in edit mode");
+
+        let tag = tag.trim_start_matches(['+', '@', '#']).to_string();
+        if self.apply(Action::Tag, false, true, |list, _| list.tag(&tag)) {
+            self.message = format!("Tagged '{tag}'.");
+        }
+    }
+
+    pub fn fold_item(&mut self, folded: Option<bool>) {
+        let res = match self.panel {
+            Panel::Todo => self.todos.fold(folded),
+            Panel::Done => self.dones.fold(folded),
+        };
+        if let Err(err) = res {
+            self.message.push_str(err);
+        }
+    }
+
+    pub fn fold_all(&mut self, folded: bool) {
+        self.todos.fold_all(folded);
+        self.dones.fold_all(folded);
+    }
+
+    pub fn select_item(&mut self) {
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.end_visual();
+        if let Err(err) = list.toggle_selected() {
+            self.message.push_str(err);
+        }
+    }
+
+    /// Toggles the visual mode of the panel, see `List::start_visual()`.
+    pub fn toggle_visual(&mut self, subtrees: bool) {
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        if !list.end_visual() {
+            list.start_visual(subtrees);
+            list.update_visual(!self.hide_subs);
+        }
+    }
+
+    pub fn is_in_visual(&self) -> bool {
+        match self.panel {
+            Panel::Todo => self.todos.visual.is_some(),
+            Panel::Done => self.dones.visual.is_some(),
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.todos.clear_selection();
+        self.dones.clear_selection();
+    }
+
     pub fn undo(&mut self) {
         assert!(!self.is_in_edit(), "Can't undo while in edit mode");
 
+        self.todos.end_visual();
+        self.dones.end_visual();
+
         let op = self.operation_stack.pop();
         match op {
             Some(op) => {
@@ -1067,6 +2537,350 @@ impl TodoApp {
         }
     }
 
+    pub fn yank_items(&mut self) -> Entries {
+        assert!(!self.is_in_edit(), "Can't yank while in edit mode");
+
+        let entries = match self.panel {
+            Panel::Todo => self.todos.yank(),
+            Panel::Done => self.dones.yank(),
+        };
+        if entries.is_empty() {
+            self.message.push_str("Can't yank item. List is empty.");
+        }
+        entries
+    }
+
+    /// Removes the selected items or the current one, along with their subtasks.
+    pub fn cut_items(&mut self) -> Entries {
+        assert!(!self.is_in_edit(), "Can't cut while in edit mode");
+
+        let mut entries = Vec::new();
+        let cut = self.apply(Action::Cut, false, false, |list, _| {
+            if list.get_cur_item().is_none() {
+                return Err("Can't cut item. List is empty.");
+            }
+            entries.append(&mut list.subtree(list.cur));
+            list.delete()?;
+            list.fix_counts();
+            Ok(())
+        });
+
+        if cut {
+            entries
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn paste_items(&mut self, mut entries: Entries, as_child: bool) {
+        assert!(!self.is_in_edit(), "Can't paste while in edit mode");
+
+        self.drop_taken_uids(&mut entries);
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => {
+                for (item, _) in entries.iter_mut().filter(|(item, _)| item.is_active()) {
+                    item.act_cnt = 0;
+                    item.date = Local::now();
+                }
+                &mut self.dones
+            }
+        };
+
+        list.record_state();
+        match list.paste(entries, as_child) {
+            Ok(()) => self
+                .operation_stack
+                .push(Operation::new(Action::Paste, self.panel)),
+            Err(err) => {
+                self.message.push_str(err);
+                list.revert_state().unwrap();
+            }
+        }
+    }
+
+    /// Copies of items can't share the ids that other items depend on.
+    fn drop_taken_uids(&self, entries: &mut Entries) {
+        for (item, _) in entries.iter_mut() {
+            let taken = self
+                .todos
+                .list
+                .iter()
+                .chain(&self.dones.list)
+                .any(|other| other.uid.is_some() && other.uid == item.uid);
+            if taken {
+                item.uid = None;
+            }
+        }
+    }
+
+    /// Makes the current item depend on the selected ones, or drops its
+    /// dependencies when nothing is selected.
+    pub fn block_item(&mut self) {
+        assert!(!self.is_in_edit(), "Can't block item while in edit mode");
+
+        if self.panel == Panel::Done {
+            self.message.push_str("Can't block done item.");
+            return;
+        }
+
+        let list = &mut self.todos;
+        list.end_visual();
+        let cur = list.cur;
+        let Some(item) = list.get_cur_item() else {
+            self.message.push_str("Can't block item. List is empty.");
+            return;
+        };
+
+        let blockers: Vec<usize> = (0..list.list.len())
+            .filter(|&i| list.list[i].selected && i != cur)
+            .collect();
+        if blockers.is_empty() {
+            if item.deps.is_empty() {
+                self.message
+                    .push_str("Select the items that block the current one first.");
+                return;
+            }
+            list.record_state();
+            list.list[cur].deps.clear();
+            self.message.push_str("Item unblocked.");
+        } else {
+            let mut ancestor = item.parent;
+            while let Some(p) = ancestor {
+                if blockers.contains(&p) {
+                    self.message.push_str("Can't block item by its parent.");
+                    return;
+                }
+                ancestor = list.list[p].parent;
+            }
+            if let Some(uid) = item.uid {
+                if blockers
+                    .iter()
+                    .any(|&b| list.depends_on(&list.list[b], uid))
+                {
+                    self.message
+                        .push_str("Can't block item. It would end up blocking itself.");
+                    return;
+                }
+            }
+
+            list.record_state();
+            let mut next_uid = self
+                .todos
+                .list
+                .iter()
+                .chain(&self.dones.list)
+                .filter_map(|item| item.uid)
+                .max()
+                .unwrap_or(0);
+            let list = &mut self.todos;
+            for &b in &blockers {
+                let uid = *list.list[b].uid.get_or_insert_with(|| {
+                    next_uid += 1;
+                    next_uid
+                });
+                if !list.list[cur].deps.contains(&uid) {
+                    list.list[cur].deps.push(uid);
+                }
+            }
+            list.clear_selection();
+            self.message = format!("Item blocked by {} item(s).", blockers.len());
+        }
+        self.operation_stack
+            .push(Operation::new(Action::Block, Panel::Todo));
+    }
+
+    /// Starts the timer of the current item, stopping any other one, or stops it.
+    pub fn toggle_timer(&mut self) {
+        assert!(!self.is_in_edit(), "Can't clock item while in edit mode");
+
+        if self.panel == Panel::Done {
+            self.message.push_str("Can't clock done item.");
+            return;
+        }
+
+        let list = &mut self.todos;
+        let cur = list.cur;
+        match list.get_cur_item() {
+            None => {
+                self.message.push_str("Can't clock item. List is empty.");
+                return;
+            }
+            Some(item) if !item.is_active() => {
+                self.message.push_str("Can't clock item. Item is done.");
+                return;
+            }
+            Some(_) => {}
+        }
+
+        list.record_state();
+        if list.list[cur].clocked_since().is_some() {
+            list.list[cur].clock_out();
+            self.message.push_str("Timer stopped.");
+        } else {
+            for item in list.list.iter_mut() {
+                item.clock_out();
+            }
+            list.list[cur].clock_in();
+            self.message.push_str("Timer started.");
+        }
+        self.operation_stack
+            .push(Operation::new(Action::Clock, Panel::Todo));
+    }
+
+    /// Stops the running timer, when one was started in another list. It is
+    /// stopped in the undo history too, so that undo can't start it again.
+    pub fn stop_timer(&mut self) {
+        let now = Local::now();
+        for list in [&mut self.todos, &mut self.dones] {
+            let history = list.state_stack.iter_mut().map(|(items, _)| items);
+            for items in iter::once(&mut list.list).chain(history) {
+                for item in items.iter_mut() {
+                    item.clock_out_at(now);
+                }
+            }
+        }
+    }
+
+    /// The item with the running timer.
+    pub fn get_timer(&self) -> Option<&Item> {
+        self.todos
+            .list
+            .iter()
+            .find(|item| item.clocked_since().is_some())
+    }
+
+    /// The item along with all of its subtasks, which follow it in the list.
+    pub fn with_subtasks(&self, item: &Item) -> &[Item] {
+        for list in [&self.todos, &self.dones] {
+            if let Some(i) = list.list.iter().position(|it| std::ptr::eq(it, item)) {
+                return &list.list[i..=i + list.children_cnt(i)];
+            }
+        }
+        &[]
+    }
+
+    /// How many of the subtasks of the item are done, out of all of them.
+    pub fn progress(&self, item: &Item) -> Option<(usize, usize)> {
+        match self.with_subtasks(item) {
+            [_, subtasks @ ..] if !subtasks.is_empty() => {
+                let done = subtasks.iter().filter(|it| !it.is_active()).count();
+                Some((done, subtasks.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The estimates of the item and its subtasks, of the active ones and of all of them.
+    pub fn effort(&self, item: &Item) -> (Effort, Effort) {
+        let items = self.with_subtasks(item);
+        let estimates = |active_only: bool| {
+            items
+                .iter()
+                .filter(|it| !active_only || it.is_active())
+                .filter_map(|it| it.estimate)
+                .sum()
+        };
+        (estimates(true), estimates(false))
+    }
+
+    /// The estimates of the work that is left and of the work that is done.
+    pub fn effort_summary(&self) -> (Effort, Effort) {
+        let (left, done): (Vec<&Item>, Vec<&Item>) = self
+            .todos
+            .list
+            .iter()
+            .chain(&self.dones.list)
+            .partition(|item| item.is_active());
+        let sum = |items: Vec<&Item>| items.iter().filter_map(|item| item.estimate).sum();
+        (sum(left), sum(done))
+    }
+
+    pub fn is_blocked(&self, item: &Item) -> bool {
+        self.todos.is_blocked(item)
+    }
+
+    pub fn is_unblocked_view(&self) -> bool {
+        self.todos.unblocked
+    }
+
+    /// Shows only the TODO items that can be marked right away.
+    pub fn toggle_unblocked_view(&mut self) {
+        self.todos.unblocked = !self.todos.unblocked;
+        self.fix_cursor();
+        if self.todos.unblocked {
+            self.message.push_str("Showing the unblocked items.");
+        } else {
+            self.message.push_str("Showing all the items.");
+        }
+    }
+
+    /// Removes the current item along with its subtasks to be put into another list.
+    pub fn take_item(&mut self, id: usize) -> Option<Entries> {
+        assert!(!self.is_in_edit(), "Can't move item while in edit mode");
+
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+
+        if list.get_cur_item().is_none() {
+            self.message.push_str("Can't move item. List is empty.");
+            return None;
+        }
+
+        list.record_state();
+        let mut entries = list.subtree(list.cur);
+        for (item, _) in entries.iter_mut() {
+            item.selected = false;
+        }
+        list.delete().unwrap();
+        list.fix_counts();
+        self.operation_stack
+            .push(Operation::new(Action::Move(id), self.panel));
+
+        Some(entries)
+    }
+
+    /// Appends the items taken from another list to the end of the given panel.
+    pub fn put_items(&mut self, mut entries: Entries, to_todos: bool, id: usize) {
+        assert!(!self.is_in_edit(), "Can't move item while in edit mode");
+
+        self.drop_taken_uids(&mut entries);
+        let panel = if to_todos { Panel::Todo } else { Panel::Done };
+        // Positions in the other file mean nothing here
+        for (item, _) in entries.iter_mut() {
+            item.pos = None;
+        }
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => {
+                for (item, _) in entries.iter_mut() {
+                    item.act_cnt = 0;
+                }
+                &mut self.dones
+            }
+        };
+
+        list.record_state();
+        let mut all = list.entries();
+        all.append(&mut entries);
+        list.rebuild(all);
+        self.operation_stack
+            .push(Operation::new(Action::Move(id), panel));
+    }
+
+    /// The id of the move between lists when it is the last action.
+    pub fn last_move(&self) -> Option<usize> {
+        match self.operation_stack.last() {
+            Some(Operation {
+                action: Action::Move(id),
+                ..
+            }) => Some(*id),
+            _ => None,
+        }
+    }
+
     pub fn insert_item(&mut self) -> Option<usize> {
         assert!(
             !self.is_in_edit(),
@@ -1082,6 +2896,7 @@ impl TodoApp {
                     Ok(()) => {
                         self.operation_stack
                             .push(Operation::new(Action::Insert, Panel::Todo));
+                        self.todos.pin_cur = true;
                         editing_cursor = Some(0);
 
                         self.operation_stack
@@ -1117,6 +2932,7 @@ impl TodoApp {
                     Ok(()) => {
                         self.operation_stack
                             .push(Operation::new(Action::Append, Panel::Todo));
+                        self.todos.pin_cur = true;
                         editing_cursor = Some(0);
 
                         self.operation_stack
@@ -1151,11 +2967,13 @@ impl TodoApp {
             match self.panel {
                 Panel::Todo => {
                     self.todos.record_state();
+                    self.todos.pin_cur = true;
                     self.operation_stack
                         .push(Operation::new(Action::Edit, Panel::Todo));
                 }
                 Panel::Done => {
                     self.dones.record_state();
+                    self.dones.pin_cur = true;
                     self.operation_stack
                         .push(Operation::new(Action::Edit, Panel::Done));
                 }
@@ -1212,7 +3030,10 @@ impl TodoApp {
                             _ => unreachable!(),
                         }
                     }
-                    self.todos.get_cur_item_mut().unwrap().trim_text();
+                    if let Some(item) = self.todos.get_cur_item_mut() {
+                        item.trim_text();
+                        item.parse_meta();
+                    }
                 }
             }
             Panel::Done => {
@@ -1222,10 +3043,13 @@ impl TodoApp {
                         return false;
                     }
                     cur_done.trim_text();
+                    cur_done.parse_meta();
                 }
             }
         }
 
+        self.todos.pin_cur = false;
+        self.dones.pin_cur = false;
         self.operation_stack.pop();
         true
     }
@@ -1238,3 +3062,645 @@ impl TodoApp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str) -> TodoApp {
+        let mut app = TodoApp::new();
+        app.read(text.as_bytes(), "test", Format::List).unwrap();
+        app
+    }
+
+    /// The texts of the items, indented by two spaces a level.
+    fn texts<'a>(items: impl Iterator<Item = (&'a Item, usize)>) -> Vec<String> {
+        items
+            .map(|(item, level)| format!("{}{}", "  ".repeat(level), item.text))
+            .collect()
+    }
+
+    fn round_trip(text: &str, format: Format) -> String {
+        let mut app = TodoApp::new();
+        app.read(text.as_bytes(), "test", format).unwrap();
+        let mut out = Vec::new();
+        app.write(&mut out, format, Progress::Off).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn markdown_round_trip() {
+        let text = "\
+Intro line.
+
+- [ ] first
+- [x] checked
+- [ ] last
+
+## Done section
+- [x] a
+- [x] b
+
+## Open section
+```
+- [ ] not an item
+```
+- [ ] c
+  - [x] d
+";
+        assert_eq!(round_trip(text, Format::Markdown), text);
+    }
+
+    #[test]
+    fn org_round_trip() {
+        let text = "\
+#+TITLE: Plan
+* DONE Old thing
+  CLOSED: [2024-03-01 Fri 10:00]
+* TODO [#A] Now :work:
+  :PROPERTIES:
+  :ID: 42
+  :END:
+** DONE Sub
+   CLOSED: [2024-03-02 Sat 09:30] SCHEDULED: <2024-03-01 Fri>
+** TODO Other
+* Project
+Body text.
+** DONE x
+   CLOSED: [2024-03-03 Sun 08:00]
+";
+        assert_eq!(round_trip(text, Format::Org), text);
+    }
+
+    fn comment(path: &str, line: usize, text: &str) -> Comment {
+        Comment {
+            path: path.to_string(),
+            line,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn merge_scanned() {
+        let mut app = TodoApp::new();
+        let comments = vec![
+            comment("src/a.rs", 3, "Fix it"),
+            comment("src/a.rs", 9, "Fix it"),
+            comment("lib/b.rs", 1, "FIXME: Leak pri:A"),
+        ];
+        let stats = app.merge_scanned(".", comments);
+        assert_eq!((stats.added, stats.updated, stats.resolved), (3, 0, 0));
+        let leak = app.iter_todos().nth(2).unwrap().0;
+        assert_eq!(leak.get_text(), "FIXME: Leak");
+        assert_eq!(leak.get_priority(), Some('A'));
+
+        // Same comments twice in a file keep their own items
+        let comments = vec![
+            comment("src/a.rs", 4, "Fix it"),
+            comment("src/a.rs", 9, "Fix it"),
+            comment("lib/b.rs", 1, "FIXME: Leak pri:A"),
+        ];
+        let stats = app.merge_scanned(".", comments);
+        assert_eq!((stats.added, stats.updated, stats.resolved), (0, 1, 0));
+        let lines: Vec<usize> = app
+            .iter_todos()
+            .map(|(item, _)| item.get_source().unwrap().1)
+            .collect();
+        assert_eq!(lines, [4, 9, 1]);
+    }
+
+    #[test]
+    fn merge_scanned_resolves_in_scope() {
+        let mut app = TodoApp::new();
+        let comments = vec![comment("src/a.rs", 1, "One"), comment("lib/b.rs", 1, "Two")];
+        app.merge_scanned(".", comments);
+
+        // Rescanning `src` leaves the comments of `lib` alone
+        let stats = app.merge_scanned("./src", Vec::new());
+        assert_eq!((stats.added, stats.updated, stats.resolved), (0, 0, 1));
+        let active: Vec<bool> = app.iter_todos().map(|(item, _)| item.is_active()).collect();
+        assert_eq!(active, [false, true]);
+
+        // A comment that comes back is found among the done items
+        app.transfer_item();
+        let stats = app.merge_scanned("src", vec![comment("src/a.rs", 2, "One")]);
+        assert_eq!((stats.added, stats.updated, stats.resolved), (0, 1, 0));
+        assert_eq!(texts(app.iter_dones()), ["One"]);
+    }
+
+    #[test]
+    fn rescan_path_with_spaces() {
+        let mut app = TodoApp::new();
+        let stats = app.merge_scanned(".", vec![comment("my dir/a b.rs", 3, "Fix it")]);
+        assert_eq!(stats.added, 1);
+
+        let mut out = Vec::new();
+        app.write(&mut out, Format::List, Progress::Off).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(" src:my%20dir/a%20b.rs:3\n"), "{text}");
+
+        let mut app = load(&text);
+        let stats = app.merge_scanned(".", vec![comment("my dir/a b.rs", 5, "Fix it")]);
+        assert_eq!((stats.added, stats.updated, stats.resolved), (0, 1, 0));
+        let item = app.iter_todos().next().unwrap().0;
+        assert_eq!(item.get_source(), Some((&"my dir/a b.rs".to_string(), 5)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut app = TodoApp::new();
+        let err = app
+            .parse_list("[package]\n".as_bytes(), "bad.list")
+            .unwrap_err();
+        assert_eq!(err, "bad.list:1: invalid format for a TODO item");
+
+        let text = format!("TODO(*): a\n{SEP}\n{SEP}\n");
+        let err = TodoApp::new()
+            .parse_list(text.as_bytes(), "bad.list")
+            .unwrap_err();
+        assert_eq!(err, "bad.list:3: invalid separator");
+    }
+
+    #[test]
+    fn bulk_mark_and_undo() {
+        let mut app = load(&format!("TODO(*): a\nTODO(*): b\nTODO(*): c\n{SEP}\n"));
+        app.select_item();
+        app.go_down();
+        app.go_down();
+        app.select_item();
+        app.mark_item();
+        let active: Vec<bool> = app.iter_todos().map(|(item, _)| item.is_active()).collect();
+        assert_eq!(active, [false, true, false]);
+
+        // A bulk operation is undone in one step
+        app.undo();
+        assert!(app.iter_todos().all(|(item, _)| item.is_active()));
+    }
+
+    #[test]
+    fn visual_transfer() {
+        let mut app = load(&format!(
+            "TODO(*): a\nTODO(*): b\n    TODO(*): b1\nTODO(*): c\n{SEP}\n"
+        ));
+        app.go_down();
+        app.toggle_visual(false);
+        app.go_down();
+        app.go_down();
+        // As after every key in the main loop
+        app.fix_cursor();
+        assert!(app.is_in_visual());
+        let selected: Vec<bool> = app
+            .iter_todos()
+            .map(|(item, _)| item.is_selected())
+            .collect();
+        assert_eq!(selected, [false, true, true, true]);
+
+        // The selection is used up by the operation
+        app.mark_item();
+        assert!(!app.is_in_visual());
+        assert!(app.iter_todos().all(|(item, _)| !item.is_selected()));
+
+        // Subtasks go with their parents
+        app.go_top();
+        app.go_down();
+        app.toggle_visual(false);
+        app.go_down();
+        app.go_down();
+        app.fix_cursor();
+        app.transfer_item();
+        assert!(!app.is_in_visual());
+        assert_eq!(texts(app.iter_todos()), ["a"]);
+        assert_eq!(texts(app.iter_dones()), ["b", "  b1", "c"]);
+    }
+
+    #[test]
+    fn bulk_tag_and_drag() {
+        let mut app = load(&format!("TODO(*): a\nTODO(*): b\nTODO(*): c\n{SEP}\n"));
+        app.toggle_visual(false);
+        app.go_down();
+        app.fix_cursor();
+        app.tag_items("+work");
+        assert_eq!(texts(app.iter_todos()), ["a +work", "b +work", "c"]);
+
+        // The selected items move together
+        app.toggle_visual(false);
+        app.go_up();
+        app.fix_cursor();
+        app.drag_down(1);
+        assert_eq!(texts(app.iter_todos()), ["c", "a +work", "b +work"]);
+
+        app.select_item();
+        app.clear_selection();
+        assert!(app.iter_todos().all(|(item, _)| !item.is_selected()));
+    }
+
+    #[test]
+    fn indent_and_outdent() {
+        let mut app = load(&format!(
+            "TODO(*): a\nTODO(*): b\n    TODO(*): b1\nTODO(*): c\n{SEP}\n"
+        ));
+        app.go_down();
+        app.indent_item(1);
+        assert_eq!(texts(app.iter_todos()), ["a", "  b", "    b1", "c"]);
+
+        // There is nothing under `a` above `b` anymore
+        app.indent_item(1);
+        assert!(app.get_message().contains("no item above"));
+        assert_eq!(texts(app.iter_todos()), ["a", "  b", "    b1", "c"]);
+
+        // An outdented item becomes the next sibling of its old parent
+        app.go_down();
+        app.outdent_item(1);
+        assert_eq!(texts(app.iter_todos()), ["a", "  b", "  b1", "c"]);
+
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["a", "  b", "    b1", "c"]);
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["a", "b", "  b1", "c"]);
+    }
+
+    #[test]
+    fn outdent_selected() {
+        let mut app = load(&format!(
+            "TODO(*): a\n    TODO(*): a1\n    TODO(*): a2\n        TODO(*): a2x\nTODO(*): b\n{SEP}\n"
+        ));
+        app.go_down();
+        app.toggle_visual(false);
+        app.go_down();
+        app.fix_cursor();
+        app.outdent_item(1);
+        assert_eq!(texts(app.iter_todos()), ["a", "a1", "a2", "  a2x", "b"]);
+
+        app.outdent_item(1);
+        assert!(app.get_message().contains("already a root"));
+    }
+
+    #[test]
+    fn yank_and_paste() {
+        let mut app = load(&format!(
+            "TODO(*): a\nTODO(*): b\n    TODO(*): b1\nTODO(*): c\n{SEP}\n"
+        ));
+        app.go_down();
+        let entries = app.yank_items();
+        assert_eq!(entries.len(), 2);
+
+        // Below the subtree of the current item, or as its last subtasks
+        app.go_down();
+        app.go_down();
+        app.paste_items(entries.clone(), false);
+        assert_eq!(
+            texts(app.iter_todos()),
+            ["a", "b", "  b1", "c", "b", "  b1"]
+        );
+        app.go_top();
+        app.paste_items(entries, true);
+        assert_eq!(
+            texts(app.iter_todos()),
+            ["a", "  b", "    b1", "b", "  b1", "c", "b", "  b1"]
+        );
+
+        app.undo();
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["a", "b", "  b1", "c"]);
+    }
+
+    #[test]
+    fn cut_and_paste_done() {
+        let mut app = load(&format!(
+            "TODO(*): a\n    TODO(*): a1\nTODO(*): b\n{SEP}\nDONE(2024-03-01 10:00 +0000): c\n"
+        ));
+        let entries = app.cut_items();
+        assert_eq!(texts(app.iter_todos()), ["b"]);
+
+        // The items pasted into DONE are done
+        app.toggle_panel();
+        app.paste_items(entries, false);
+        assert_eq!(texts(app.iter_dones()), ["c", "a", "  a1"]);
+        assert!(app.iter_dones().all(|(item, _)| !item.is_active()));
+
+        app.undo();
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["a", "  a1", "b"]);
+        assert_eq!(texts(app.iter_dones()), ["c"]);
+    }
+
+    #[test]
+    fn fold_items() {
+        let mut app = load(&format!(
+            "TODO(*): a\n    TODO(*): a1\n        TODO(*): a1x\nTODO(*): b\n{SEP}\n"
+        ));
+        // An item without subtasks folds its parent
+        app.go_down();
+        app.go_down();
+        app.fold_item(Some(true));
+        app.fix_cursor();
+        assert_eq!(texts(app.iter_todos()), ["a", "  a1", "b"]);
+
+        app.go_down();
+        app.fold_item(None);
+        assert!(app.get_message().contains("no subtasks"));
+        app.go_up();
+        app.fold_item(None);
+        assert_eq!(texts(app.iter_todos()), ["a", "  a1", "    a1x", "b"]);
+
+        app.fold_all(true);
+        assert_eq!(texts(app.iter_todos()), ["a", "b"]);
+        let mut out = Vec::new();
+        app.write(&mut out, Format::List, Progress::Off).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "TODO(*): a fold:closed\n    TODO(*): a1 fold:closed\n        TODO(*): a1x\nTODO(*): b\n{SEP}\n"
+            )
+        );
+        app.fold_all(false);
+        assert_eq!(texts(app.iter_todos()).len(), 4);
+    }
+
+    #[test]
+    fn folds_are_saved() {
+        let text = format!("TODO(*): a fold:closed\n    TODO(*): a1\nTODO(*): b\n{SEP}\n");
+        assert_eq!(round_trip(&text, Format::List), text);
+
+        // Items without subtasks have nothing to fold
+        let text = format!("TODO(*): a fold:closed\n{SEP}\n");
+        assert_eq!(
+            round_trip(&text, Format::List),
+            format!("TODO(*): a\n{SEP}\n")
+        );
+    }
+
+    #[test]
+    fn todotxt_recurrence() {
+        let text = "Water due:2024-03-01 rec:1w\nCall rec:+1d\nPay rec:1y\n";
+        assert_eq!(round_trip(text, Format::TodoTxt), text);
+
+        let mut app = TodoApp::new();
+        app.read(text.as_bytes(), "test", Format::TodoTxt).unwrap();
+        let mut out = Vec::new();
+        app.write(&mut out, Format::List, Progress::Off).unwrap();
+        let list = String::from_utf8(out).unwrap();
+        assert!(list.contains("Water due:2024-03-01 rec:weekly\n"), "{list}");
+        assert!(list.contains("Call rec:+1d\n"), "{list}");
+    }
+
+    #[test]
+    fn block_and_mark() {
+        let mut app = load(&format!("TODO(*): a\nTODO(*): b\nTODO(*): c\n{SEP}\n"));
+        app.select_item();
+        app.go_down();
+        app.select_item();
+        app.go_down();
+        app.block_item();
+        let meta: Vec<String> = app
+            .iter_todos()
+            .map(|(item, _)| item.meta().join(" "))
+            .collect();
+        assert_eq!(meta, ["uid:1", "uid:2", "dep:1,2"]);
+
+        app.mark_item();
+        assert!(app.get_message().contains("blocked"));
+        app.toggle_unblocked_view();
+        assert_eq!(texts(app.iter_todos()), ["a", "b"]);
+        app.toggle_unblocked_view();
+
+        // Once all its dependencies are done
+        app.go_top();
+        app.mark_item();
+        app.go_down();
+        app.mark_item();
+        app.go_down();
+        app.mark_item();
+        assert!(app.iter_todos().all(|(item, _)| !item.is_active()));
+    }
+
+    #[test]
+    fn block_errors() {
+        let mut app = load(&format!(
+            "TODO(*): a uid:1\n    TODO(*): a1\nTODO(*): b dep:1\n{SEP}\n"
+        ));
+        app.block_item();
+        assert!(app.get_message().contains("Select the items"));
+
+        // `a` would wait for `b` which waits for `a`
+        app.go_down();
+        app.go_down();
+        app.select_item();
+        app.go_top();
+        app.block_item();
+        assert!(app.get_message().contains("blocking itself"));
+
+        app.clear_selection();
+        app.select_item();
+        app.go_down();
+        app.block_item();
+        assert!(app.get_message().contains("by its parent"));
+
+        // Without a selection the dependencies are dropped
+        app.clear_selection();
+        app.go_down();
+        app.block_item();
+        assert!(app.iter_todos().all(|(item, _)| !app.is_blocked(item)));
+        app.undo();
+        let meta: Vec<String> = app
+            .iter_todos()
+            .map(|(item, _)| item.meta().join(" "))
+            .collect();
+        assert_eq!(meta, ["uid:1", "", "dep:1"]);
+    }
+
+    #[test]
+    fn copies_drop_taken_uids() {
+        let mut app = load(&format!("TODO(*): a uid:1\nTODO(*): b dep:1\n{SEP}\n"));
+        let entries = app.yank_items();
+        app.paste_items(entries, false);
+        let meta: Vec<String> = app
+            .iter_todos()
+            .map(|(item, _)| item.meta().join(" "))
+            .collect();
+        assert_eq!(meta, ["uid:1", "", "dep:1"]);
+
+        // A cut item takes its id along
+        let mut app = load(&format!("TODO(*): a uid:1\nTODO(*): b dep:1\n{SEP}\n"));
+        let entries = app.cut_items();
+        app.paste_items(entries, false);
+        let meta: Vec<String> = app
+            .iter_todos()
+            .map(|(item, _)| item.meta().join(" "))
+            .collect();
+        assert_eq!(meta, ["dep:1", "uid:1"]);
+    }
+
+    #[test]
+    fn stop_timer() {
+        let mut app = load(&format!("TODO(*): b\nTODO(*): a\n{SEP}\n"));
+        app.toggle_timer();
+        app.drag_down(1);
+        assert!(app.get_timer().is_some());
+
+        // A timer started in another list, undo keeps this one stopped
+        app.stop_timer();
+        assert!(app.get_timer().is_none());
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["b", "a"]);
+        assert!(app.get_timer().is_none());
+        app.undo();
+        assert!(app.get_timer().is_none());
+    }
+
+    #[test]
+    fn board_columns() {
+        let mut app = load(&format!(
+            "TODO(*): a\nTODO(*): b state:doing\n    TODO(*): b1\nTODO(*): c state:gone\n{SEP}\n"
+        ));
+        let names = ["To do", "Doing", "Done"].map(String::from);
+        app.set_columns(&names);
+        // Unknown states go to the first column
+        assert_eq!(texts(app.iter_column(0)), ["a", "c"]);
+        assert_eq!(texts(app.iter_column(1)), ["b", "  b1"]);
+
+        // Active items go through the columns before DONE
+        app.transfer_item();
+        assert_eq!(texts(app.iter_column(1)), ["a", "b", "  b1"]);
+        assert_eq!(app.get_column(), Some(0));
+        app.toggle_panel();
+        app.fix_cursor();
+        assert_eq!(app.get_column(), Some(1));
+        app.transfer_item();
+        assert!(app.get_message().contains("still active"));
+        app.mark_item();
+        app.transfer_item();
+        assert_eq!(texts(app.iter_column(1)), ["b", "  b1"]);
+        assert_eq!(texts(app.iter_dones()), ["a"]);
+
+        // And come back to the last one
+        app.toggle_panel();
+        app.transfer_back();
+        assert_eq!(texts(app.iter_column(1)), ["b", "  b1", "a"]);
+        let states: Vec<String> = app
+            .iter_column(0)
+            .chain(app.iter_column(1))
+            .map(|(item, _)| item.meta().join(" "))
+            .collect();
+        assert_eq!(states, ["state:gone", "state:doing", "", "state:doing"]);
+    }
+
+    #[test]
+    fn board_transfer_back() {
+        let mut app = load(&format!("TODO(*): a state:doing\nTODO(*): b\n{SEP}\n"));
+        let names = ["To do", "Doing", "Done"].map(String::from);
+        app.set_columns(&names);
+        app.fix_cursor();
+        app.transfer_back();
+        assert!(app.get_message().contains("first column"));
+
+        app.toggle_panel();
+        app.fix_cursor();
+        app.transfer_back();
+        assert_eq!(texts(app.iter_column(0)), ["a", "b"]);
+        assert!(app.iter_column(0).all(|(item, _)| item.meta().is_empty()));
+        app.undo();
+        assert_eq!(texts(app.iter_column(1)), ["a"]);
+
+        // Without a board there is nowhere to go back to
+        let mut app = load(&format!("TODO(*): a\n{SEP}\n"));
+        app.transfer_back();
+        assert!(app.get_message().contains("not done yet"));
+    }
+
+    #[test]
+    fn archive_old_dones() {
+        let mut app = load(&format!(
+            "TODO(*): a\n{SEP}\nDONE(2024-01-15 10:00 +0000): b\n    DONE(2024-03-02 10:00 +0000): b1\nDONE(2024-03-01 10:00 +0000): c\n"
+        ));
+        app.mark_item();
+
+        // A failed write keeps the items
+        let before = NaiveDate::from_ymd_opt(2024, 2, 1);
+        let err = app.archive(before, |_| Err(io::Error::other("disk full")));
+        assert!(err.is_err());
+        assert_eq!(texts(app.iter_dones()), ["b", "  b1", "c"]);
+
+        // Subtasks go along with their roots whenever they were done
+        let mut archived = Vec::new();
+        let n = app.archive(before, |entries| {
+            archived = entries;
+            Ok(())
+        });
+        assert_eq!(n.unwrap(), 1);
+        assert_eq!(
+            texts(archived.iter().map(|(item, level)| (item, *level))),
+            ["b", "  b1"]
+        );
+        assert_eq!(texts(app.iter_dones()), ["c"]);
+
+        // The archived items can't come back by undo
+        app.undo();
+        assert_eq!(texts(app.iter_dones()), ["c"]);
+        assert_eq!(app.archive(before, |_| Ok(())).unwrap(), 0);
+        assert_eq!(app.archive(None, |_| Ok(())).unwrap(), 1);
+        assert_eq!(texts(app.iter_dones()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sort_keeps_subtrees() {
+        let mut app = load(&format!(
+            "TODO(*): c\nTODO(*): B\n    TODO(*): b2\n    TODO(*): b1\nTODO(*): a\n{SEP}\n"
+        ));
+        app.sort_items(SortKey::Text, false);
+        assert_eq!(texts(app.iter_todos()), ["a", "B", "  b2", "  b1", "c"]);
+        // The cursor stays on the same item
+        let cur: Vec<bool> = app
+            .iter_todos()
+            .map(|(item, _)| app.is_cur_todo(item))
+            .collect();
+        assert_eq!(cur, [false, false, false, false, true]);
+
+        app.sort_items(SortKey::Text, true);
+        assert_eq!(texts(app.iter_todos()), ["a", "B", "  b1", "  b2", "c"]);
+
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["a", "B", "  b2", "  b1", "c"]);
+        app.undo();
+        assert_eq!(texts(app.iter_todos()), ["c", "B", "  b2", "  b1", "a"]);
+    }
+
+    #[test]
+    fn sort_keys() {
+        let mut app = load(&format!(
+            "TODO(*): a\nTODO(*): b pri:B due:2024-05-01\n    TODO(*): b1\nTODO(*): c pri:A\n    TODO(*): c1\n        TODO(*): c2\nTODO(*): d due:2024-04-01\n{SEP}\n"
+        ));
+        let roots = |app: &TodoApp| -> Vec<String> {
+            app.iter_todos()
+                .filter(|(_, level)| *level == 0)
+                .map(|(item, _)| item.get_text().clone())
+                .collect()
+        };
+
+        // The items without the key go last
+        app.sort_items(SortKey::Priority, false);
+        assert_eq!(roots(&app), ["c", "b", "a", "d"]);
+        app.sort_items(SortKey::Due, false);
+        assert_eq!(roots(&app), ["d", "b", "c", "a"]);
+        app.sort_items(SortKey::Subtasks, false);
+        assert_eq!(roots(&app), ["c", "b", "d", "a"]);
+
+        assert_eq!("pri".parse::<SortKey>(), Ok(SortKey::Priority));
+        assert!("size".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn iter_matching() {
+        let mut app = load(&format!(
+            "TODO(*): parent fold:closed\n    TODO(*): child +work\nTODO(*): other\n{SEP}\n"
+        ));
+        assert_eq!(texts(app.iter_todos()), ["parent", "other"]);
+        assert_eq!(
+            texts(app.iter_matching()),
+            ["parent", "  child +work", "other"]
+        );
+
+        app.set_filter(Some("work".parse().unwrap()));
+        assert_eq!(texts(app.iter_matching()), ["parent", "  child +work"]);
+    }
+}
@@ -85,6 +85,7 @@ struct Layout {
     size: Vec2,
     max_size: Vec2,
     children: Vec<LayoutRef>,
+    slots: Option<i32>,
 }
 
 impl Layout {
@@ -95,6 +96,7 @@ impl Layout {
             max_size,
             size: Vec2::default(),
             children: Vec::new(),
+            slots: None,
         }
     }
 
@@ -102,6 +104,9 @@ impl Layout {
         let child_size = self.available_size().0;
 
         match self.kind {
+            LayoutKind::Horz if self.slots.is_some() => {
+                self.pos + child_size * Vec2::new(self.children.len() as i32, 0)
+            }
             LayoutKind::Horz => {
                 let x = min(self.size.x, child_size.x);
                 self.pos + Vec2::new(x, 0)
@@ -111,6 +116,13 @@ impl Layout {
     }
 
     fn available_size(&self) -> (Vec2, Vec2) {
+        // The remainder only goes to the last slot, the others would overlap
+        if let Some(n) = self.slots {
+            let (size, rem) = self.max_size.div_rem(Vec2::new(n, 1));
+            let last = self.children.len() as i32 + 1 >= n;
+            return (size, if last { rem } else { Vec2::new(0, rem.y) });
+        }
+
         let div = self.children.len() as i32 + 1;
         match self.kind {
             LayoutKind::Horz => self.max_size.div_rem(Vec2::new(div, 1)),
@@ -189,6 +201,14 @@ impl UI {
         self.stack.push(Rc::new(RefCell::new(child)));
     }
 
+    /// Starts a horizontal layout split into `n` columns of the same width, to be
+    /// filled with one layout each.
+    pub fn begin_columns(&mut self, n: usize) {
+        self.begin_layout(LayoutKind::Horz);
+        let layout = self.stack.last().unwrap();
+        layout.borrow_mut().slots = Some(n as i32);
+    }
+
     pub fn br(&mut self) {
         let layout = self
             .stack
@@ -1,11 +1,20 @@
+use std::cmp::min;
 use std::env::args;
+use std::io::{self, Write};
 use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use ncurses::*;
 
+use super::cmdline::COMMANDS;
+use super::config::{Colors, Config};
+use super::format::{Entries, Format};
+use super::keymap::Keymap;
+use super::query::Query;
+
 use crate::{
-    FILE_PATH, FPS, HELP, HELP_PAIR, HIGHLIGHT_PAIR, SELECTED_PAIR, UI_PAIR, UNSELECTED_PAIR, USAGE,
+    FPS, HELP, HELP_PAIR, HIGHLIGHT_PAIR, MARKED_PAIR, SELECTED_PAIR, UI_PAIR, UNSELECTED_PAIR,
+    USAGE,
 };
 
 static CTRLC: AtomicBool = AtomicBool::new(false);
@@ -16,7 +25,8 @@ extern "C" fn callback(_signum: i32) {
 
 pub fn sig_handler_init() {
     unsafe {
-        if libc::signal(libc::SIGINT, callback as libc::sighandler_t) == libc::SIG_ERR {
+        if libc::signal(libc::SIGINT, callback as *const () as libc::sighandler_t) == libc::SIG_ERR
+        {
             unreachable!()
         }
     }
@@ -26,7 +36,7 @@ pub fn ctrlc_poll() -> bool {
     CTRLC.swap(false, Ordering::Relaxed)
 }
 
-pub fn ncurses_init() {
+pub fn ncurses_init(colors: &Colors) {
     setlocale(LcCategory::all, "");
     // Init ncurses
     initscr();
@@ -41,34 +51,133 @@ pub fn ncurses_init() {
     // Set colors
     use_default_colors();
     start_color();
-    init_pair(HIGHLIGHT_PAIR, COLOR_BLACK, COLOR_GREEN);
-    init_pair(SELECTED_PAIR, COLOR_BLACK, COLOR_CYAN);
-    init_pair(UNSELECTED_PAIR, COLOR_BLACK, COLOR_WHITE);
-    init_pair(UI_PAIR, COLOR_WHITE, COLOR_BLACK);
-    init_pair(HELP_PAIR, COLOR_WHITE, COLOR_BLACK);
+    // The last colors are redefined for the '#rrggbb' ones when the terminal allows it
+    let mut free_slot = if can_change_color() {
+        min(COLORS(), i16::MAX as i32) as i16 - 1
+    } else {
+        -1
+    };
+    for (pair, colors) in [
+        (HIGHLIGHT_PAIR, colors.highlight),
+        (SELECTED_PAIR, colors.selected),
+        (UNSELECTED_PAIR, colors.unselected),
+        (UI_PAIR, colors.ui),
+        (HELP_PAIR, colors.help),
+        (MARKED_PAIR, colors.marked),
+    ] {
+        let fg = colors.fg.resolve(&mut free_slot);
+        let bg = colors.bg.resolve(&mut free_slot);
+        init_pair(pair, fg, bg);
+    }
+}
+
+pub enum Subcommand {
+    App,
+    Scan(String),
+    Stats,
+    List(Option<Query>),
+}
+
+pub struct Args {
+    pub command: Subcommand,
+    pub file_paths: Vec<String>,
+    pub format: Option<Format>,
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("[ERROR]: {msg}");
+    eprintln!("{USAGE}");
+    exit(1);
+}
+
+pub fn controls(keymap: &Keymap) -> Vec<(String, String)> {
+    let mut controls = keymap.help();
+    controls.push((
+        "<count><key>".to_string(),
+        "Repeat a move/drag/indent/undo <count> times".to_string(),
+    ));
+    controls.push((
+        "<enter>, <esc>".to_string(),
+        "Save/Cancel edited item".to_string(),
+    ));
+    controls.push((
+        "<tab>, <up>, <down>".to_string(),
+        "Complete/Recall a ':' command".to_string(),
+    ));
+    for (name, arg, description) in COMMANDS {
+        controls.push((
+            format!(":{name} {arg}").trim_end().to_string(),
+            description.to_string(),
+        ));
+    }
+    controls
 }
 
-pub fn get_args() -> String {
+fn help(keymap: &Keymap) -> String {
+    let controls: Vec<String> = controls(keymap)
+        .into_iter()
+        .map(|(keys, description)| format!("        {keys:<32}~ {description}"))
+        .collect();
+    format!("{HELP}\n    Controls:\n{}\n", controls.join("\n"))
+}
+
+pub fn get_args(config: &Config) -> Args {
     let mut args = args().skip(1);
+    let mut command = Subcommand::App;
+    let mut file_paths = Vec::new();
+    let mut format = None;
+    let mut query = None;
 
-    match args.next() {
-        Some(arg) => match arg.as_str() {
-            "-f" | "--file" => args.next().unwrap_or_else(|| {
-                eprintln!("[ERROR]: No file given for '{arg}'.");
-                eprintln!("{USAGE}");
-                exit(1);
-            }),
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "scan" => {
+                let dir = args
+                    .next()
+                    .unwrap_or_else(|| usage_error(&format!("No directory given for '{arg}'.")));
+                command = Subcommand::Scan(dir);
+            }
+            "stats" => command = Subcommand::Stats,
+            "list" => command = Subcommand::List(None),
+            "--where" => {
+                let expr = args
+                    .next()
+                    .unwrap_or_else(|| usage_error(&format!("No filter given for '{arg}'.")));
+                query = Some(config.query(&expr).unwrap_or_else(|err| usage_error(&err)));
+            }
+            "-f" | "--file" => {
+                file_paths.push(
+                    args.next()
+                        .unwrap_or_else(|| usage_error(&format!("No file given for '{arg}'."))),
+                );
+            }
+            "--format" => {
+                let fmt = args
+                    .next()
+                    .unwrap_or_else(|| usage_error(&format!("No format given for '{arg}'.")));
+                format = Some(fmt.parse().unwrap_or_else(|err: String| usage_error(&err)));
+            }
             "-h" | "--help" => {
-                println!("{HELP}\n{USAGE}");
+                println!("{}\n{USAGE}", help(&config.keymap));
                 exit(0);
             }
-            _ => {
-                eprintln!("[ERROR]: Unknown argument: '{arg}'.");
-                eprintln!("{USAGE}");
-                exit(1);
-            }
-        },
-        None => FILE_PATH.to_string(),
+            _ => usage_error(&format!("Unknown argument: '{arg}'.")),
+        }
+    }
+
+    if file_paths.is_empty() {
+        file_paths.push(config.list.clone());
+    }
+    if query.is_some() {
+        match &mut command {
+            Subcommand::List(q) => *q = query,
+            _ => usage_error("'--where' only goes with 'list'."),
+        }
+    }
+
+    Args {
+        command,
+        file_paths,
+        format,
     }
 }
 
@@ -78,3 +187,70 @@ pub fn truncate(s: &str, max_chars: usize) -> &str {
         Some((idx, _)) => &s[..idx],
     }
 }
+
+pub fn edit_text(text: &mut String, cur: &mut usize, key: i32) {
+    *cur = min(*cur, text.len());
+
+    match key {
+        32..=126 => {
+            if *cur > text.len() {
+                text.push(key as u8 as char);
+            } else {
+                text.insert(*cur, key as u8 as char);
+            }
+            *cur += 1;
+        }
+        constants::KEY_LEFT if *cur > 0 => *cur -= 1,
+        constants::KEY_RIGHT if *cur < text.len() => *cur += 1,
+        // 127 is backspace
+        constants::KEY_BACKSPACE | 127 if *cur > 0 => {
+            *cur -= 1;
+            if *cur < text.len() {
+                text.remove(*cur);
+            }
+        }
+        constants::KEY_DC if *cur < text.len() => {
+            text.remove(*cur);
+        }
+        constants::KEY_HOME | 1 => *cur = 0, // 1 is ctrl + a
+        constants::KEY_END | 5 => *cur = text.len(), // 5 is ctrl + e
+        _ => {}
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Puts the items into the system clipboard with the OSC 52 terminal sequence,
+/// the subtasks indented by `indent_size` spaces a level.
+pub fn copy_to_clipboard(entries: &Entries, indent_size: usize) {
+    let text: Vec<String> = entries
+        .iter()
+        .map(|(item, level)| format!("{}{}", " ".repeat(level * indent_size), item.get_text()))
+        .collect();
+
+    let mut stdout = io::stdout();
+    let _ = write!(
+        stdout,
+        "\x1b]52;c;{}\x07",
+        base64(text.join("\n").as_bytes())
+    );
+    let _ = stdout.flush();
+}